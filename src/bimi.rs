@@ -0,0 +1,252 @@
+use crate::parse::join_txt_strings;
+use crate::spoofability::Enforcement;
+use crate::tag_list::{DiagnosticKind, TagList};
+use crate::uri::{UriList, HTTPS_SCHEME};
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use std::fmt;
+use trust_dns_client::rr::Record;
+
+const DEFAULT_SELECTOR: &str = "default";
+const BIMI_LABEL: &str = "_bimi";
+
+const BIMI1: &str = "BIMI1";
+const V_TAG: &str = "v";
+const L_TAG: &str = "l";
+const A_TAG: &str = "a";
+
+const FULL_PCT: u8 = 100;
+
+/// Name the default BIMI record of `domain_name` is published at.
+pub fn record_name(domain_name: &str) -> String {
+    format!(
+        "{}.{}.{}",
+        DEFAULT_SELECTOR,
+        BIMI_LABEL,
+        domain_name.trim_end_matches('.')
+    )
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BimiFinding {
+    NoRecord,
+    MultipleRecords(usize),
+    SyntaxError(String),
+    BadUri(String),
+    Declined,
+    NoEvidence,
+    NoDmarcPolicy,
+    PolicyNone,
+    PartialPct(u8),
+    SubdomainPolicyNone,
+}
+
+impl fmt::Display for BimiFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoRecord => write!(f, "No BIMI record"),
+            Self::MultipleRecords(n) => write!(f, "{} BIMI records: No logo is shown", n),
+            Self::SyntaxError(s) => write!(f, "{}", s),
+            Self::BadUri(s) => write!(f, "{}", s),
+            Self::Declined => write!(f, "Empty `l` and `a`: Domain declines to show a logo"),
+            Self::NoEvidence => write!(
+                f,
+                "No `a` certificate: Mailbox providers requiring a mark certificate show no logo"
+            ),
+            Self::NoDmarcPolicy => write!(f, "No DMARC policy applies: No logo is shown"),
+            Self::PolicyNone => write!(
+                f,
+                "DMARC policy is `none`: Logos need `quarantine` or `reject`"
+            ),
+            Self::PartialPct(pct) => write!(
+                f,
+                "DMARC pct={}: Logos need the policy applied to all mail",
+                pct
+            ),
+            Self::SubdomainPolicyNone => write!(f, "sp=none: Logos need subdomains enforced too"),
+        }
+    }
+}
+
+impl BimiFinding {
+    /// Findings that keep receivers from showing the logo at all.
+    pub fn is_invalid(&self) -> bool {
+        !matches!(self, Self::NoRecord | Self::Declined | Self::NoEvidence)
+    }
+}
+
+/// The default BIMI record of a domain and what is wrong with it.
+#[derive(Debug, Default, PartialEq)]
+pub struct BimiCheck {
+    pub logo: Option<String>,
+    pub evidence: Option<String>,
+    pub findings: Vec<BimiFinding>,
+}
+
+impl BimiCheck {
+    /// Selects the record from the TXT answers at `default._bimi.<domain>`.
+    pub fn from_answers(r: &[Record]) -> Self {
+        let txts: Vec<String> = r
+            .iter()
+            .filter_map(|record| record.rdata().as_txt())
+            .map(join_txt_strings)
+            .filter(|txt| txt.starts_with(&format!("{}={}", V_TAG, BIMI1)))
+            .collect();
+
+        match txts.len() {
+            0 => Self::new(vec![BimiFinding::NoRecord]),
+            1 => Self::from_txt(&txts[0]),
+            n => Self::new(vec![BimiFinding::MultipleRecords(n)]),
+        }
+    }
+
+    pub fn from_txt(txt: &str) -> Self {
+        let mut tag_list = TagList::parse(txt);
+        tag_list.check_version_first(V_TAG);
+
+        // Empty `l=` and `a=` are how a domain declines to publish, not syntax errors
+        let mut check = Self::new(
+            tag_list
+                .diagnostics
+                .iter()
+                .filter(|d| !matches!(d.kind, DiagnosticKind::EmptyValue(_)))
+                .map(|d| BimiFinding::SyntaxError(d.to_string()))
+                .collect(),
+        );
+
+        let tag = |name: &str| {
+            tag_list
+                .tags
+                .iter()
+                .find(|t| t.name == name)
+                .map(|t| t.value.trim())
+                .filter(|value| !value.is_empty())
+        };
+
+        if let Some(v) = tag(V_TAG).filter(|v| *v != BIMI1) {
+            check.findings.push(BimiFinding::SyntaxError(format!(
+                "v={} is not {}",
+                v, BIMI1
+            )));
+        }
+
+        check.logo = tag(L_TAG).map(String::from);
+        check.evidence = tag(A_TAG).map(String::from);
+
+        // Logos and certificates are only fetched over https
+        for uri in check.logo.iter().chain(check.evidence.iter()) {
+            check.findings.extend(
+                UriList::parse(uri, &[HTTPS_SCHEME])
+                    .findings
+                    .iter()
+                    .map(|f| BimiFinding::BadUri(f.to_string())),
+            );
+        }
+
+        match (&check.logo, &check.evidence) {
+            (None, None) => check.findings.push(BimiFinding::Declined),
+            (Some(_), None) => check.findings.push(BimiFinding::NoEvidence),
+            _ => (),
+        }
+
+        check
+    }
+
+    /// Checks the DMARC policy applied to the domain is strict enough for a logo to show:
+    /// `quarantine` or `reject` for all mail, and not `sp=none`.
+    pub fn check_dmarc(
+        &mut self,
+        policy: Option<Enforcement>,
+        pct: u8,
+        subdomain_policy: Option<Enforcement>,
+    ) {
+        match policy {
+            None => self.findings.push(BimiFinding::NoDmarcPolicy),
+            Some(Enforcement::None) => self.findings.push(BimiFinding::PolicyNone),
+            Some(_) if pct < FULL_PCT => self.findings.push(BimiFinding::PartialPct(pct)),
+            Some(_) => (),
+        }
+
+        if subdomain_policy == Some(Enforcement::None) && policy != Some(Enforcement::None) {
+            self.findings.push(BimiFinding::SubdomainPolicyNone);
+        }
+    }
+
+    /// Whether a record was found, even one declining to show a logo.
+    pub fn has_record(&self) -> bool {
+        !self.findings.contains(&BimiFinding::NoRecord)
+    }
+
+    fn new(findings: Vec<BimiFinding>) -> Self {
+        Self {
+            findings,
+            ..Self::default()
+        }
+    }
+}
+
+// Tests
+#[test]
+fn bimi_record() {
+    let check = BimiCheck::from_txt(
+        "v=BIMI1; l=https://example.com/logo.svg; a=https://example.com/vmc.pem",
+    );
+    assert_eq!(
+        check,
+        BimiCheck {
+            logo: Some("https://example.com/logo.svg".to_string()),
+            evidence: Some("https://example.com/vmc.pem".to_string()),
+            findings: vec![],
+        }
+    );
+
+    assert_eq!(
+        BimiCheck::from_txt("v=BIMI1; l=http://example.com/logo.svg").findings,
+        vec![
+            BimiFinding::BadUri("Unsupported URI scheme `http://example.com/logo.svg`".to_string()),
+            BimiFinding::NoEvidence,
+        ]
+    );
+    assert_eq!(
+        BimiCheck::from_txt("v=BIMI1; l=; a=;").findings,
+        vec![BimiFinding::Declined]
+    );
+    assert_eq!(
+        BimiCheck::from_answers(&[]).findings,
+        vec![BimiFinding::NoRecord]
+    );
+    assert_eq!(record_name("example.com."), "default._bimi.example.com");
+}
+
+#[test]
+fn bimi_check_dmarc() {
+    let check_dmarc = |policy, pct, subdomain_policy| {
+        let mut check = BimiCheck::default();
+        check.check_dmarc(policy, pct, subdomain_policy);
+        check.findings
+    };
+
+    assert_eq!(
+        check_dmarc(
+            Some(Enforcement::Reject),
+            100,
+            Some(Enforcement::Quarantine)
+        ),
+        vec![]
+    );
+    assert_eq!(
+        check_dmarc(Some(Enforcement::Quarantine), 50, Some(Enforcement::None)),
+        vec![
+            BimiFinding::PartialPct(50),
+            BimiFinding::SubdomainPolicyNone
+        ]
+    );
+    assert_eq!(
+        check_dmarc(Some(Enforcement::None), 100, Some(Enforcement::None)),
+        vec![BimiFinding::PolicyNone]
+    );
+    assert_eq!(
+        check_dmarc(None, 100, None),
+        vec![BimiFinding::NoDmarcPolicy]
+    );
+}
@@ -0,0 +1,867 @@
+use crate::parse::join_txt_strings;
+#[cfg(test)]
+use crate::resolver::StaticResolver;
+use crate::resolver::{ResolveError, Resolver};
+use crate::spf::{DualCidr, Mechanism, Qualifier, SpfRecord};
+use crate::spf_expansion::{MAX_DNS_LOOKUPS, MAX_MX_NAMES, MAX_VOID_LOOKUPS};
+use crate::uri::is_domain_name;
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use std::fmt;
+use std::net::IpAddr;
+use trust_dns_client::rr::RecordType;
+
+const POSTMASTER: &str = "postmaster";
+
+/// Only the first 10 names of a PTR answer are validated, RFC 7208 §5.5.
+const MAX_PTR_NAMES: usize = 10;
+const MAX_DOMAIN_LEN: usize = 253;
+
+const MACRO_DELIMITERS: &str = ".-+,/_=";
+const DEFAULT_DELIMITER: &str = ".";
+const UNKNOWN_DOMAIN: &str = "unknown";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpfResult {
+    None,
+    Neutral,
+    Pass,
+    Fail,
+    SoftFail,
+    TempError,
+    PermError,
+}
+
+impl fmt::Display for SpfResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Neutral => write!(f, "neutral"),
+            Self::Pass => write!(f, "pass"),
+            Self::Fail => write!(f, "fail"),
+            Self::SoftFail => write!(f, "softfail"),
+            Self::TempError => write!(f, "temperror"),
+            Self::PermError => write!(f, "permerror"),
+        }
+    }
+}
+
+impl From<Qualifier> for SpfResult {
+    fn from(qualifier: Qualifier) -> Self {
+        match qualifier {
+            Qualifier::Pass => Self::Pass,
+            Qualifier::Fail => Self::Fail,
+            Qualifier::SoftFail => Self::SoftFail,
+            Qualifier::Neutral => Self::Neutral,
+        }
+    }
+}
+
+/// The result of `check_host` and what decided it.
+#[derive(Debug, PartialEq)]
+pub struct SpfVerdict {
+    pub result: SpfResult,
+    pub reason: String,
+}
+
+impl fmt::Display for SpfVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.result, self.reason)
+    }
+}
+
+impl SpfVerdict {
+    fn new<S: ToString>(result: SpfResult, reason: S) -> Self {
+        Self {
+            result,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Evaluates SPF for mail from `ip` with the given MAIL FROM and HELO, see RFC 7208 §4.
+///
+/// An empty MAIL FROM, as used by bounces, is checked as `postmaster@<helo>`.
+pub fn check_host<R: Resolver>(
+    resolver: &R,
+    ip: IpAddr,
+    mail_from: &str,
+    helo: &str,
+) -> SpfVerdict {
+    // IPv4 mapped addresses are evaluated as the IPv4 address, RFC 7208 §5
+    let ip = match ip {
+        IpAddr::V6(ip6) => ip6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        ip => ip,
+    };
+
+    let (local_part, sender_domain) = match mail_from.rsplit_once('@') {
+        Some((local_part, domain)) if !local_part.is_empty() => (local_part, domain),
+        Some((_, domain)) => (POSTMASTER, domain),
+        None if mail_from.is_empty() => (POSTMASTER, helo),
+        None => (POSTMASTER, mail_from),
+    };
+
+    let mut evaluation = Evaluation {
+        resolver,
+        ip,
+        local_part: local_part.to_string(),
+        sender_domain: normalize(sender_domain),
+        helo: helo.to_string(),
+        lookups: 0,
+        void_lookups: 0,
+        validated_names: None,
+    };
+
+    let sender_domain = evaluation.sender_domain.clone();
+    evaluation.check(&sender_domain)
+}
+
+struct Evaluation<'a, R: Resolver> {
+    resolver: &'a R,
+    ip: IpAddr,
+    local_part: String,
+    sender_domain: String,
+    helo: String,
+    lookups: usize,
+    void_lookups: usize,
+    validated_names: Option<Vec<String>>,
+}
+
+impl<'a, R: Resolver> Evaluation<'a, R> {
+    fn check(&mut self, domain: &str) -> SpfVerdict {
+        let domain = normalize(domain);
+
+        if !is_domain_name(&domain) {
+            return SpfVerdict::new(
+                SpfResult::None,
+                format!("`{}` is not a domain name", domain),
+            );
+        }
+
+        let answers = match self.lookup(&domain, RecordType::TXT) {
+            Ok(answers) => answers,
+            Err(verdict) => return verdict,
+        };
+
+        let spf_txts: Vec<String> = answers
+            .iter()
+            .filter_map(|r| r.rdata().as_txt())
+            .map(join_txt_strings)
+            .filter(|txt| SpfRecord::is_spf(txt))
+            .collect();
+
+        let spf_record = match spf_txts.len() {
+            0 => return SpfVerdict::new(SpfResult::None, format!("No SPF record at `{}`", domain)),
+            1 => SpfRecord::parse(&spf_txts[0]),
+            n => {
+                return SpfVerdict::new(
+                    SpfResult::PermError,
+                    format!("{} SPF records at `{}`", n, domain),
+                )
+            }
+        };
+
+        if let Some(e) = spf_record.errors.first() {
+            return SpfVerdict::new(SpfResult::PermError, format!("{} at `{}`", e, domain));
+        }
+
+        for directive in &spf_record.directives {
+            match self.matches(&domain, &directive.mechanism) {
+                Ok(true) => {
+                    return SpfVerdict::new(
+                        directive.qualifier.into(),
+                        format!("Matched `{}` at `{}`", directive, domain),
+                    )
+                }
+                Ok(false) => (),
+                Err(verdict) => return verdict,
+            }
+        }
+
+        // Reached only when there is no `all` mechanism, which always matches
+        if let Some(redirect) = &spf_record.redirect {
+            let target = match self
+                .count_lookup()
+                .and_then(|_| self.expand(redirect, &domain))
+            {
+                Ok(target) => target,
+                Err(verdict) => return verdict,
+            };
+
+            let verdict = self.check(&target);
+
+            return match verdict.result {
+                SpfResult::None => SpfVerdict::new(
+                    SpfResult::PermError,
+                    format!("No SPF record at redirect target `{}`", target),
+                ),
+                _ => verdict,
+            };
+        }
+
+        SpfVerdict::new(
+            SpfResult::Neutral,
+            format!("No mechanism matched at `{}`", domain),
+        )
+    }
+
+    /// Whether the sender IP matches `mechanism`, an error ends the whole evaluation.
+    fn matches(&mut self, domain: &str, mechanism: &Mechanism) -> Result<bool, SpfVerdict> {
+        match mechanism {
+            Mechanism::All => Ok(true),
+            Mechanism::Ip4(network, prefix) => {
+                Ok(in_network(self.ip, IpAddr::V4(*network), *prefix))
+            }
+            Mechanism::Ip6(network, prefix) => {
+                Ok(in_network(self.ip, IpAddr::V6(*network), *prefix))
+            }
+            Mechanism::Include(domain_spec) => {
+                self.count_lookup()?;
+                let target = self.expand(domain_spec, domain)?;
+                let verdict = self.check(&target);
+
+                match verdict.result {
+                    SpfResult::Pass => Ok(true),
+                    SpfResult::Fail | SpfResult::SoftFail | SpfResult::Neutral => Ok(false),
+                    SpfResult::TempError | SpfResult::PermError => Err(verdict),
+                    SpfResult::None => Err(SpfVerdict::new(
+                        SpfResult::PermError,
+                        format!("No SPF record at include target `{}`", target),
+                    )),
+                }
+            }
+            Mechanism::A(domain_spec, cidr) => {
+                self.count_lookup()?;
+                let target = self.target(domain_spec, domain)?;
+                let addresses = self.addresses(&target)?;
+
+                if addresses.is_empty() {
+                    self.count_void_lookup()?;
+                }
+
+                Ok(addresses.iter().any(|a| self.in_dual_network(*a, cidr)))
+            }
+            Mechanism::Mx(domain_spec, cidr) => {
+                self.count_lookup()?;
+                let target = self.target(domain_spec, domain)?;
+                let exchanges: Vec<String> = self
+                    .lookup(&target, RecordType::MX)?
+                    .iter()
+                    .filter_map(|r| r.rdata().as_mx())
+                    .map(|mx| mx.exchange().to_string())
+                    .collect();
+
+                if exchanges.is_empty() {
+                    self.count_void_lookup()?;
+                }
+
+                if exchanges.len() > MAX_MX_NAMES {
+                    return Err(SpfVerdict::new(
+                        SpfResult::PermError,
+                        format!("{} MX names at `{}`", exchanges.len(), target),
+                    ));
+                }
+
+                for exchange in exchanges {
+                    if self
+                        .addresses(&exchange)?
+                        .iter()
+                        .any(|a| self.in_dual_network(*a, cidr))
+                    {
+                        return Ok(true);
+                    }
+                }
+
+                Ok(false)
+            }
+            Mechanism::Ptr(domain_spec) => {
+                self.count_lookup()?;
+                let target = self.target(domain_spec, domain)?;
+
+                Ok(self
+                    .validated_names()
+                    .iter()
+                    .any(|name| is_same_or_subdomain(name, &target)))
+            }
+            Mechanism::Exists(domain_spec) => {
+                self.count_lookup()?;
+                let target = self.expand(domain_spec, domain)?;
+
+                // exists always queries A records, whatever the version of the sender IP
+                match self.lookup(&target, RecordType::A)?.is_empty() {
+                    true => self.count_void_lookup().map(|_| false),
+                    false => Ok(true),
+                }
+            }
+        }
+    }
+
+    fn count_lookup(&mut self) -> Result<(), SpfVerdict> {
+        self.lookups += 1;
+
+        match self.lookups > MAX_DNS_LOOKUPS {
+            true => Err(SpfVerdict::new(
+                SpfResult::PermError,
+                format!("More than {} DNS lookups", MAX_DNS_LOOKUPS),
+            )),
+            false => Ok(()),
+        }
+    }
+
+    fn count_void_lookup(&mut self) -> Result<(), SpfVerdict> {
+        self.void_lookups += 1;
+
+        match self.void_lookups > MAX_VOID_LOOKUPS {
+            true => Err(SpfVerdict::new(
+                SpfResult::PermError,
+                format!("More than {} void lookups", MAX_VOID_LOOKUPS),
+            )),
+            false => Ok(()),
+        }
+    }
+
+    fn lookup(
+        &self,
+        name: &str,
+        record_type: RecordType,
+    ) -> Result<Vec<trust_dns_client::rr::Record>, SpfVerdict> {
+        match self.resolver.lookup_type(name, record_type) {
+            Ok(answers) => Ok(answers),
+            // A name that cannot be queried has no records
+            Err(ResolveError::InvalidName(_)) => Ok(Vec::new()),
+            Err(e) => Err(SpfVerdict::new(SpfResult::TempError, e)),
+        }
+    }
+
+    /// Addresses of `name` in the same version as the sender IP.
+    fn addresses(&self, name: &str) -> Result<Vec<IpAddr>, SpfVerdict> {
+        let record_type = match self.ip {
+            IpAddr::V4(_) => RecordType::A,
+            IpAddr::V6(_) => RecordType::AAAA,
+        };
+
+        Ok(self
+            .lookup(name, record_type)?
+            .iter()
+            .filter_map(|r| {
+                r.rdata()
+                    .as_a()
+                    .map(|a| IpAddr::V4(*a))
+                    .or_else(|| r.rdata().as_aaaa().map(|aaaa| IpAddr::V6(*aaaa)))
+            })
+            .collect())
+    }
+
+    /// Names the sender IP points back to that also resolve to the sender IP, RFC 7208 §5.5.
+    ///
+    /// The PTR and address lookups are made once per evaluation, so every `ptr` and `%{p}`
+    /// shares the same 10 address lookups, RFC 7208 §4.6.4.
+    fn validated_names(&mut self) -> Vec<String> {
+        if let Some(names) = &self.validated_names {
+            return names.clone();
+        }
+
+        let names: Vec<String> = self
+            .lookup(&reverse_name(self.ip), RecordType::PTR)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|r| r.rdata().as_ptr())
+            .map(|name| normalize(&name.to_string()))
+            .take(MAX_PTR_NAMES)
+            .collect();
+
+        // A name that fails to resolve is skipped rather than failing the check
+        let names: Vec<String> = names
+            .into_iter()
+            .filter(|name| {
+                self.addresses(name)
+                    .map(|addresses| addresses.contains(&self.ip))
+                    .unwrap_or(false)
+            })
+            .collect();
+
+        self.validated_names = Some(names.clone());
+        names
+    }
+
+    fn in_dual_network(&self, address: IpAddr, cidr: &DualCidr) -> bool {
+        match address {
+            IpAddr::V4(_) => in_network(self.ip, address, cidr.ip4),
+            IpAddr::V6(_) => in_network(self.ip, address, cidr.ip6),
+        }
+    }
+
+    /// The domain a mechanism applies to, the current domain when none is given.
+    fn target(&mut self, domain_spec: &Option<String>, domain: &str) -> Result<String, SpfVerdict> {
+        match domain_spec {
+            Some(domain_spec) => self.expand(domain_spec, domain),
+            None => Ok(domain.to_string()),
+        }
+    }
+
+    /// Expands the macros in a domain-spec, see RFC 7208 §7.
+    fn expand(&mut self, domain_spec: &str, domain: &str) -> Result<String, SpfVerdict> {
+        let mut expanded = String::new();
+        let mut chars = domain_spec.chars().peekable();
+        let bad_macro = || {
+            SpfVerdict::new(
+                SpfResult::PermError,
+                format!("Bad macro in `{}`", domain_spec),
+            )
+        };
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                expanded.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('%') => expanded.push('%'),
+                Some('_') => expanded.push(' '),
+                Some('-') => expanded.push_str("%20"),
+                Some('{') => {
+                    let letter = chars.next().ok_or_else(bad_macro)?;
+
+                    let mut digits = String::new();
+                    while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                        digits.push(*d);
+                        chars.next();
+                    }
+
+                    let reverse = chars.peek().is_some_and(|r| r.eq_ignore_ascii_case(&'r'));
+                    if reverse {
+                        chars.next();
+                    }
+
+                    let mut delimiters = String::new();
+                    while let Some(d) = chars.peek().filter(|d| MACRO_DELIMITERS.contains(**d)) {
+                        delimiters.push(*d);
+                        chars.next();
+                    }
+
+                    if chars.next() != Some('}') {
+                        return Err(bad_macro());
+                    }
+
+                    let keep = match digits.is_empty() {
+                        true => None,
+                        false => match digits.parse::<usize>() {
+                            Ok(n) if n > 0 => Some(n),
+                            _ => return Err(bad_macro()),
+                        },
+                    };
+
+                    // %{p} queries PTR records, which count against the lookup limit when
+                    // no `ptr` or earlier %{p} has made the query
+                    if letter.eq_ignore_ascii_case(&'p') && self.validated_names.is_none() {
+                        self.count_lookup()?;
+                    }
+
+                    let value = self
+                        .macro_value(letter.to_ascii_lowercase(), domain)
+                        .ok_or_else(bad_macro)?;
+                    let value = transform(&value, keep, reverse, &delimiters);
+
+                    match letter.is_ascii_uppercase() {
+                        true => expanded.push_str(&url_escape(&value)),
+                        false => expanded.push_str(&value),
+                    }
+                }
+                _ => return Err(bad_macro()),
+            }
+        }
+
+        Ok(truncate_domain(&expanded))
+    }
+
+    /// Value of a macro letter, `c`, `r` and `t` are only allowed in explanations.
+    fn macro_value(&mut self, letter: char, domain: &str) -> Option<String> {
+        match letter {
+            's' => Some(format!("{}@{}", self.local_part, self.sender_domain)),
+            'l' => Some(self.local_part.clone()),
+            'o' => Some(self.sender_domain.clone()),
+            'd' => Some(domain.to_string()),
+            'i' => Some(match self.ip {
+                IpAddr::V4(ip4) => ip4.to_string(),
+                IpAddr::V6(_) => nibbles(self.ip).join("."),
+            }),
+            'p' => {
+                let names = self.validated_names();
+
+                Some(
+                    names
+                        .iter()
+                        .find(|name| is_same_or_subdomain(name, domain))
+                        .or_else(|| names.first())
+                        .cloned()
+                        .unwrap_or_else(|| UNKNOWN_DOMAIN.to_string()),
+                )
+            }
+            'v' => Some(
+                match self.ip {
+                    IpAddr::V4(_) => "in-addr",
+                    IpAddr::V6(_) => "ip6",
+                }
+                .to_string(),
+            ),
+            'h' => Some(self.helo.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Splits on the delimiters, optionally reverses and keeps the rightmost parts, joined by dots.
+fn transform(value: &str, keep: Option<usize>, reverse: bool, delimiters: &str) -> String {
+    let delimiters = match delimiters.is_empty() {
+        true => DEFAULT_DELIMITER,
+        false => delimiters,
+    };
+
+    let mut parts: Vec<&str> = value.split(|c| delimiters.contains(c)).collect();
+
+    if reverse {
+        parts.reverse();
+    }
+
+    let skip = keep.map_or(0, |n| parts.len().saturating_sub(n));
+    parts[skip..].join(".")
+}
+
+fn url_escape(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Drops labels from the left until the name fits in 253 characters, RFC 7208 §7.3.
+fn truncate_domain(domain: &str) -> String {
+    let mut domain = domain;
+
+    while domain.len() > MAX_DOMAIN_LEN {
+        match domain.find('.') {
+            Some(i) => domain = &domain[i + 1..],
+            None => break,
+        }
+    }
+
+    domain.to_string()
+}
+
+/// Hex digits of an IPv6 address, most significant first.
+fn nibbles(ip: IpAddr) -> Vec<String> {
+    match ip {
+        IpAddr::V4(_) => Vec::new(),
+        IpAddr::V6(ip6) => ip6
+            .octets()
+            .iter()
+            .flat_map(|o| vec![format!("{:x}", o >> 4), format!("{:x}", o & 0xf)])
+            .collect(),
+    }
+}
+
+/// The `in-addr.arpa` or `ip6.arpa` name of an address.
+fn reverse_name(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip4) => {
+            let o = ip4.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", o[3], o[2], o[1], o[0])
+        }
+        IpAddr::V6(_) => {
+            let mut nibbles = nibbles(ip);
+            nibbles.reverse();
+            format!("{}.ip6.arpa", nibbles.join("."))
+        }
+    }
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+fn is_same_or_subdomain(name: &str, domain: &str) -> bool {
+    name == domain || name.ends_with(&format!(".{}", domain))
+}
+
+fn normalize(domain: &str) -> String {
+    domain.trim_end_matches('.').to_lowercase()
+}
+
+// Tests
+#[cfg(test)]
+fn check(resolver: &StaticResolver, ip: &str, mail_from: &str) -> SpfResult {
+    check_host(resolver, ip.parse().unwrap(), mail_from, "mail.example.org").result
+}
+
+#[test]
+fn check_host_results() {
+    let resolver = StaticResolver::default()
+        .txt(
+            "example.com",
+            "v=spf1 ip4:192.0.2.0/24 ip6:2001:db8::/32 include:_spf.example.net ?ip4:198.51.100.1 -all",
+        )
+        .txt("_spf.example.net", "v=spf1 a:mail.example.net ~all")
+        .a("mail.example.net", "203.0.113.7")
+        .txt("soft.example.com", "v=spf1 ~all")
+        .txt("neutral.example.com", "v=spf1 mx")
+        .txt("two.example.com", "v=spf1 -all")
+        .txt("two.example.com", "v=spf1 +all")
+        .txt("bad.example.com", "v=spf1 ip4:192.0.2.300 -all")
+        .txt("redirect.example.com", "v=spf1 redirect=example.com")
+        .txt("include.example.com", "v=spf1 include:none.example.com -all")
+        .timeout("slow.example.com");
+
+    assert_eq!(
+        check(&resolver, "192.0.2.10", "a@example.com"),
+        SpfResult::Pass
+    );
+    assert_eq!(
+        check(&resolver, "2001:db8::1", "a@example.com"),
+        SpfResult::Pass
+    );
+    assert_eq!(
+        check(&resolver, "::ffff:192.0.2.10", "a@example.com"),
+        SpfResult::Pass
+    );
+    assert_eq!(
+        check(&resolver, "203.0.113.7", "a@example.com"),
+        SpfResult::Pass
+    );
+    assert_eq!(
+        check(&resolver, "198.51.100.1", "a@example.com"),
+        SpfResult::Neutral
+    );
+    assert_eq!(
+        check(&resolver, "198.51.100.2", "a@example.com"),
+        SpfResult::Fail
+    );
+    assert_eq!(
+        check(&resolver, "198.51.100.2", "a@soft.example.com"),
+        SpfResult::SoftFail
+    );
+    assert_eq!(
+        check(&resolver, "198.51.100.2", "a@neutral.example.com"),
+        SpfResult::Neutral
+    );
+    assert_eq!(
+        check(&resolver, "198.51.100.2", "a@other.example.com"),
+        SpfResult::None
+    );
+    assert_eq!(
+        check(&resolver, "198.51.100.2", "a@localhost"),
+        SpfResult::None
+    );
+    assert_eq!(
+        check(&resolver, "198.51.100.2", "a@two.example.com"),
+        SpfResult::PermError
+    );
+    assert_eq!(
+        check(&resolver, "198.51.100.2", "a@bad.example.com"),
+        SpfResult::PermError
+    );
+    assert_eq!(
+        check(&resolver, "192.0.2.10", "a@redirect.example.com"),
+        SpfResult::Pass
+    );
+    assert_eq!(
+        check(&resolver, "192.0.2.10", "a@include.example.com"),
+        SpfResult::PermError
+    );
+    assert_eq!(
+        check(&resolver, "192.0.2.10", "a@slow.example.com"),
+        SpfResult::TempError
+    );
+
+    // An empty MAIL FROM is checked against the HELO name
+    let resolver = resolver.txt("mail.example.org", "v=spf1 ip4:192.0.2.10 -all");
+    assert_eq!(
+        check_host(
+            &resolver,
+            "192.0.2.10".parse().unwrap(),
+            "",
+            "mail.example.org"
+        ),
+        SpfVerdict::new(
+            SpfResult::Pass,
+            "Matched `ip4:192.0.2.10/32` at `mail.example.org`"
+        )
+    );
+}
+
+#[test]
+fn check_host_dns_mechanisms() {
+    let resolver = StaticResolver::default()
+        .txt(
+            "example.com",
+            "v=spf1 a/24 mx ptr:example.com exists:%{ir}._ip.%{d} -all",
+        )
+        .a("example.com", "192.0.2.1")
+        .mx("example.com", 10, "mx.example.com")
+        .a("mx.example.com", "198.51.100.25")
+        .ptr("7.113.0.203.in-addr.arpa", "mail.example.com")
+        .a("mail.example.com", "203.0.113.7")
+        .ptr("8.113.0.203.in-addr.arpa", "mail.example.com")
+        .a("9.113.0.203._ip.example.com", "127.0.0.2")
+        .txt("ip.example.com", "v=spf1 ip4:198.51.100.1 -all")
+        .txt(
+            "limit.example.com",
+            &format!("v=spf1{} -all", " include:ip.example.com".repeat(11)),
+        )
+        .txt(
+            "void.example.com",
+            "v=spf1 a:a1.example.com a:a2.example.com a:a3.example.com -all",
+        );
+
+    assert_eq!(
+        check(&resolver, "192.0.2.200", "a@example.com"),
+        SpfResult::Pass
+    );
+    assert_eq!(
+        check(&resolver, "198.51.100.25", "a@example.com"),
+        SpfResult::Pass
+    );
+    assert_eq!(
+        check(&resolver, "203.0.113.7", "a@example.com"),
+        SpfResult::Pass
+    );
+    // PTR points at a name that does not resolve back to the address
+    assert_eq!(
+        check(&resolver, "203.0.113.8", "a@example.com"),
+        SpfResult::Fail
+    );
+    assert_eq!(
+        check(&resolver, "203.0.113.9", "a@example.com"),
+        SpfResult::Pass
+    );
+    assert_eq!(
+        check_host(
+            &resolver,
+            "192.0.2.10".parse().unwrap(),
+            "a@limit.example.com",
+            ""
+        ),
+        SpfVerdict::new(SpfResult::PermError, "More than 10 DNS lookups")
+    );
+    assert_eq!(
+        check_host(
+            &resolver,
+            "192.0.2.10".parse().unwrap(),
+            "a@void.example.com",
+            ""
+        ),
+        SpfVerdict::new(SpfResult::PermError, "More than 2 void lookups")
+    );
+
+    // %{p} counts as a lookup, here the eleventh
+    let resolver = resolver.txt(
+        "p.example.com",
+        &format!(
+            "v=spf1{} exists:%{{p}}._spf.%{{d}} -all",
+            " include:ip.example.com".repeat(9)
+        ),
+    );
+    assert_eq!(
+        check_host(
+            &resolver,
+            "203.0.113.7".parse().unwrap(),
+            "a@p.example.com",
+            ""
+        ),
+        SpfVerdict::new(SpfResult::PermError, "More than 10 DNS lookups")
+    );
+
+    // Once `ptr` made the query, neither %{p} counts again, leaving exactly 10 lookups
+    let resolver = resolver.txt(
+        "p2.example.com",
+        &format!(
+            "v=spf1{} ptr:other.example.com exists:%{{p}}.%{{p}}._spf.%{{d}} -all",
+            " include:ip.example.com".repeat(8)
+        ),
+    );
+    assert_eq!(
+        check_host(
+            &resolver,
+            "203.0.113.7".parse().unwrap(),
+            "a@p2.example.com",
+            ""
+        ),
+        SpfVerdict::new(SpfResult::Fail, "Matched `-all` at `p2.example.com`")
+    );
+}
+
+#[test]
+fn check_host_macro_expansion() {
+    let resolver = StaticResolver::default();
+    let mut evaluation = Evaluation {
+        resolver: &resolver,
+        ip: "192.0.2.3".parse().unwrap(),
+        local_part: "strong-bad".to_string(),
+        sender_domain: "email.example.com".to_string(),
+        helo: "mx.example.org".to_string(),
+        lookups: 0,
+        void_lookups: 0,
+        validated_names: None,
+    };
+    let mut expand = |domain_spec: &str| evaluation.expand(domain_spec, "email.example.com");
+
+    // Examples from RFC 7208 §7.4
+    assert_eq!(
+        expand("%{s}"),
+        Ok("strong-bad@email.example.com".to_string())
+    );
+    assert_eq!(expand("%{o}"), Ok("email.example.com".to_string()));
+    assert_eq!(expand("%{d2}"), Ok("example.com".to_string()));
+    assert_eq!(expand("%{d1}"), Ok("com".to_string()));
+    assert_eq!(expand("%{dr}"), Ok("com.example.email".to_string()));
+    assert_eq!(expand("%{d2r}"), Ok("example.email".to_string()));
+    assert_eq!(expand("%{l-}"), Ok("strong.bad".to_string()));
+    assert_eq!(expand("%{lr-}"), Ok("bad.strong".to_string()));
+    assert_eq!(expand("%{l1r-}"), Ok("strong".to_string()));
+    assert_eq!(
+        expand("%{ir}.%{v}._spf.%{d2}"),
+        Ok("3.2.0.192.in-addr._spf.example.com".to_string())
+    );
+    assert_eq!(
+        expand("%{lr-}.lp._spf.%{d2}"),
+        Ok("bad.strong.lp._spf.example.com".to_string())
+    );
+    assert_eq!(
+        expand("%{S}"),
+        Ok("strong-bad%40email.example.com".to_string())
+    );
+    assert_eq!(expand("%{h}%%%_%-"), Ok("mx.example.org% %20".to_string()));
+    assert_eq!(expand("%{p}"), Ok("unknown".to_string()));
+    assert_eq!(
+        expand("%{c}.example.com").map_err(|v| v.result),
+        Err(SpfResult::PermError)
+    );
+    assert_eq!(
+        expand("%{d0}").map_err(|v| v.result),
+        Err(SpfResult::PermError)
+    );
+
+    evaluation.ip = "2001:db8::cb01".parse().unwrap();
+    assert_eq!(
+        evaluation.expand("%{ir}.%{v}._spf.%{d2}", "email.example.com"),
+        Ok(
+            "1.0.b.c.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6._spf.example.com"
+                .to_string()
+        )
+    );
+}
@@ -0,0 +1,174 @@
+#[cfg(test)]
+use crate::parse::txt_record;
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use std::fmt;
+use trust_dns_client::rr::{Record, RecordType};
+
+/// Maximum number of CNAME records followed from the queried name.
+pub const MAX_CNAME_DEPTH: usize = 8;
+
+const CHAIN_SEPARATOR: &str = " -> ";
+
+#[derive(Debug, PartialEq)]
+pub enum ChainStatus {
+    Complete,
+    Loop(String),
+    DepthExceeded,
+}
+
+/// Next step after following the CNAME records of a DNS answer.
+#[derive(Debug, PartialEq)]
+pub enum ChainStep {
+    Done,
+    Query(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CnameChain {
+    pub names: Vec<String>,
+    pub status: ChainStatus,
+    queried: Vec<String>,
+}
+
+impl fmt::Display for CnameChain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.names.join(CHAIN_SEPARATOR))
+    }
+}
+
+impl CnameChain {
+    pub fn new(name: &str) -> Self {
+        Self {
+            names: vec![normalize(name)],
+            status: ChainStatus::Complete,
+            queried: vec![normalize(name)],
+        }
+    }
+
+    /// Follows CNAME records in `r` from the end of the chain.
+    ///
+    /// Resolvers usually return the whole chain in one answer, when they stop short the
+    /// last target still has to be queried for its own records.
+    pub fn follow(&mut self, r: &[Record]) -> ChainStep {
+        loop {
+            let current = self.names.last().cloned().unwrap_or_default();
+
+            let target = r.iter().find_map(|record| {
+                match record.rr_type() == RecordType::CNAME
+                    && normalize(&record.name().to_string()) == current
+                {
+                    true => record
+                        .rdata()
+                        .as_cname()
+                        .map(|cname| normalize(&cname.to_string())),
+                    false => None,
+                }
+            });
+
+            let target = match target {
+                Some(target) => target,
+                None => {
+                    let answered = r.iter().any(|record| {
+                        record.rr_type() != RecordType::CNAME
+                            && normalize(&record.name().to_string()) == current
+                    });
+
+                    return match answered || self.queried.contains(&current) {
+                        true => ChainStep::Done,
+                        false => {
+                            self.queried.push(current.clone());
+                            ChainStep::Query(current)
+                        }
+                    };
+                }
+            };
+
+            if self.names.contains(&target) {
+                self.status = ChainStatus::Loop(target);
+                return ChainStep::Done;
+            }
+
+            if self.depth() == MAX_CNAME_DEPTH {
+                self.status = ChainStatus::DepthExceeded;
+                return ChainStep::Done;
+            }
+
+            self.names.push(target);
+        }
+    }
+
+    /// Number of CNAME records followed.
+    pub fn depth(&self) -> usize {
+        self.names.len() - 1
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_lowercase()
+}
+
+// Tests
+#[cfg(test)]
+fn cname_record(name: &str, target: &str) -> Record {
+    use trust_dns_client::rr::{Name, RData};
+
+    Record::from_rdata(
+        Name::from_utf8(name).unwrap(),
+        300,
+        RData::CNAME(Name::from_utf8(target).unwrap()),
+    )
+}
+
+#[test]
+fn cname_chain_follow() {
+    let mut chain = CnameChain::new("_dmarc.example.com");
+    assert_eq!(chain.follow(&[]), ChainStep::Done);
+    assert_eq!(chain.depth(), 0);
+
+    let mut chain = CnameChain::new("_dmarc.example.com");
+    let answers = [
+        cname_record("_dmarc.example.com.", "example.com.dmarc.vendor.net."),
+        txt_record("example.com.dmarc.vendor.net.", "v=DMARC1; p=reject"),
+    ];
+    assert_eq!(chain.follow(&answers), ChainStep::Done);
+    assert_eq!(chain.status, ChainStatus::Complete);
+    assert_eq!(
+        chain.to_string(),
+        "_dmarc.example.com -> example.com.dmarc.vendor.net"
+    );
+
+    let mut chain = CnameChain::new("_dmarc.example.com");
+    assert_eq!(
+        chain.follow(&[cname_record("_dmarc.example.com", "dmarc.Vendor.net")]),
+        ChainStep::Query("dmarc.vendor.net".to_string())
+    );
+    assert_eq!(chain.follow(&[]), ChainStep::Done);
+    assert_eq!(chain.depth(), 1);
+}
+
+#[test]
+fn cname_chain_follow_loop_and_depth() {
+    let mut chain = CnameChain::new("_dmarc.example.com");
+    let answers = [
+        cname_record("_dmarc.example.com", "a.vendor.net"),
+        cname_record("a.vendor.net", "_dmarc.example.com"),
+    ];
+    assert_eq!(chain.follow(&answers), ChainStep::Done);
+    assert_eq!(
+        chain.status,
+        ChainStatus::Loop("_dmarc.example.com".to_string())
+    );
+
+    let mut chain = CnameChain::new("_dmarc.example.com");
+    let mut answers = vec![cname_record("_dmarc.example.com", "0.vendor.net")];
+    for i in 0..MAX_CNAME_DEPTH {
+        answers.push(cname_record(
+            &format!("{}.vendor.net", i),
+            &format!("{}.vendor.net", i + 1),
+        ));
+    }
+    assert_eq!(chain.follow(&answers), ChainStep::Done);
+    assert_eq!(chain.status, ChainStatus::DepthExceeded);
+    assert_eq!(chain.depth(), MAX_CNAME_DEPTH);
+}
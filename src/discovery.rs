@@ -0,0 +1,109 @@
+#[cfg(test)]
+use crate::parse::txt_record;
+use crate::parse::DmarcRecordType;
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use trust_dns_client::rr::Record;
+
+/// Maximum number of names the DMARCbis tree walk queries.
+pub const TREE_WALK_LIMIT: usize = 8;
+
+/// Outcome of a DMARCbis DNS tree walk policy discovery.
+#[derive(Debug, Default, PartialEq)]
+pub struct TreeWalk {
+    pub queried: Vec<String>,
+    pub policy_domain: Option<String>,
+    pub record: Option<String>,
+}
+
+impl TreeWalk {
+    /// Keeps `r` as the discovered policy if it holds exactly one DMARC record.
+    ///
+    /// Returns `true` once a record was picked and the walk can stop.
+    pub fn visit(&mut self, name: &str, r: &[Record]) -> bool {
+        self.queried.push(name.to_string());
+
+        let mut dmarc_records = r
+            .iter()
+            .filter_map(|record| DmarcRecordType::new(record).dmarc_txt());
+
+        match (dmarc_records.next(), dmarc_records.next()) {
+            (Some(record), None) => {
+                self.policy_domain = Some(name.to_string());
+                self.record = Some(record);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Names to query, starting at `domain_name` and removing a label at a time up to the TLD.
+///
+/// Names deeper than the walk limit skip straight to their last seven labels after the
+/// first query, so a walk never exceeds `TREE_WALK_LIMIT` lookups.
+pub fn tree_walk_names(domain_name: &str) -> Vec<String> {
+    let domain_name = domain_name.trim_end_matches('.').to_lowercase();
+    let labels: Vec<&str> = domain_name.split('.').collect();
+
+    let start = match labels.len() > TREE_WALK_LIMIT {
+        true => labels.len() - (TREE_WALK_LIMIT - 1),
+        false => 1,
+    };
+
+    let mut names = vec![domain_name.clone()];
+    names.extend((start..labels.len()).map(|i| labels[i..].join(".")));
+
+    names
+}
+
+// Tests
+#[test]
+fn discovery_tree_walk_names() {
+    assert_eq!(
+        tree_walk_names("Mail.Example.co.uk."),
+        vec!["mail.example.co.uk", "example.co.uk", "co.uk", "uk"]
+    );
+
+    assert_eq!(tree_walk_names("com"), vec!["com"]);
+
+    let names = tree_walk_names("a.b.c.d.e.f.g.h.i.example.com");
+    assert_eq!(names.len(), TREE_WALK_LIMIT);
+    assert_eq!(
+        names[..2],
+        ["a.b.c.d.e.f.g.h.i.example.com", "e.f.g.h.i.example.com"]
+    );
+    assert_eq!(names.last(), Some(&"com".to_string()));
+}
+
+#[test]
+fn discovery_tree_walk_visit() {
+    let dmarc_txt = |txt: &str| txt_record("_dmarc.example.com", txt);
+
+    let mut tree_walk = TreeWalk::default();
+    assert!(!tree_walk.visit("mail.example.com", &[]));
+    assert!(!tree_walk.visit(
+        "example.com",
+        &[
+            dmarc_txt("v=DMARC1; p=none"),
+            dmarc_txt("v=DMARC1; p=reject")
+        ]
+    ));
+    assert!(tree_walk.visit(
+        "example.com",
+        &[dmarc_txt("v=spf1 -all"), dmarc_txt("v=DMARC1; p=reject")]
+    ));
+
+    assert_eq!(
+        tree_walk,
+        TreeWalk {
+            queried: vec![
+                "mail.example.com".to_string(),
+                "example.com".to_string(),
+                "example.com".to_string()
+            ],
+            policy_domain: Some("example.com".to_string()),
+            record: Some("v=DMARC1; p=reject".to_string()),
+        }
+    );
+}
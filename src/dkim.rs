@@ -0,0 +1,210 @@
+use crate::parse::join_txt_strings;
+#[cfg(test)]
+use crate::parse::txt_record;
+use crate::tag_list::TagList;
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use trust_dns_client::rr::Record;
+
+/// Selectors of common mail providers shipped with the binary.
+const BUNDLED_SELECTORS: &str = include_str!("../data/dkim_selectors.txt");
+
+const COMMENT: char = '#';
+const FIELD_SEPARATOR: char = ',';
+const DOMAINKEY_LABEL: &str = "_domainkey";
+
+const P_TAG: &str = "p";
+
+/// Selectors to probe for each domain, in the order they were listed.
+#[derive(Debug, Default, PartialEq)]
+pub struct SelectorList {
+    pub selectors: Vec<String>,
+}
+
+impl SelectorList {
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED_SELECTORS)
+    }
+
+    /// Parses a newline separated list of selectors, ignoring comments and repeats.
+    pub fn parse(list: &str) -> Self {
+        let mut selector_list = Self::default();
+
+        selector_list.extend(list.lines().filter_map(|line| {
+            line.split(COMMENT)
+                .next()
+                .map(|selector| selector.trim().to_lowercase())
+                .filter(|selector| is_selector(selector))
+        }));
+
+        selector_list
+    }
+
+    pub fn extend<I: IntoIterator<Item = String>>(&mut self, selectors: I) {
+        for selector in selectors {
+            if !self.selectors.contains(&selector) {
+                self.selectors.push(selector);
+            }
+        }
+    }
+}
+
+/// Selectors are one or more labels, RFC 6376 §3.1.
+fn is_selector(selector: &str) -> bool {
+    !selector.is_empty()
+        && selector.split('.').all(|label| {
+            !label.is_empty()
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+/// Name the key record for `selector` is published at.
+pub fn key_name(selector: &str, domain_name: &str) -> String {
+    format!(
+        "{}.{}.{}",
+        selector,
+        DOMAINKEY_LABEL,
+        domain_name.trim_end_matches('.')
+    )
+}
+
+/// Selectors of a domain that answered with a DKIM key record.
+#[derive(Debug, Default, PartialEq)]
+pub struct SelectorDiscovery {
+    pub probed: usize,
+    pub found: Vec<String>,
+}
+
+impl SelectorDiscovery {
+    /// Keeps `selector` if its answers hold a key record, TXT records without a `p` tag
+    /// such as wildcards are not keys.
+    ///
+    /// Returns `true` if the selector was kept.
+    pub fn visit(&mut self, selector: &str, r: &[Record]) -> bool {
+        self.probed += 1;
+
+        let has_key = r
+            .iter()
+            .filter_map(|record| record.rdata().as_txt())
+            .map(join_txt_strings)
+            .any(|txt| {
+                TagList::parse_with_base64_tags(&txt, &[P_TAG])
+                    .tags
+                    .iter()
+                    .any(|t| t.name == P_TAG)
+            });
+
+        if has_key {
+            self.found.push(selector.to_string());
+        }
+
+        has_key
+    }
+}
+
+/// Parses the input list of domains, one per line and one output row each.
+///
+/// With `selector_fields`, each line is `domain,selector,...` and the fields after the
+/// domain name DKIM selectors to check. Otherwise the whole line is the domain.
+pub fn parse_domain_list(list: &str, selector_fields: bool) -> Vec<(String, Vec<String>)> {
+    let mut domains: Vec<(String, Vec<String>)> = Vec::new();
+
+    for line in list.lines().map(|line| line.trim()) {
+        if !selector_fields {
+            if !line.is_empty() {
+                domains.push((line.to_string(), Vec::new()));
+            }
+            continue;
+        }
+
+        let mut fields = line.split(FIELD_SEPARATOR).map(|field| field.trim());
+
+        let domain_name = match fields.next() {
+            Some(domain_name) if !domain_name.is_empty() => domain_name,
+            _ => continue,
+        };
+
+        let mut selectors: Vec<String> = Vec::new();
+        for selector in fields.map(|field| field.to_lowercase()) {
+            if is_selector(&selector) && !selectors.contains(&selector) {
+                selectors.push(selector);
+            }
+        }
+
+        domains.push((domain_name.to_string(), selectors));
+    }
+
+    domains
+}
+
+// Tests
+#[test]
+fn dkim_selector_list() {
+    let bundled = SelectorList::bundled();
+    assert!(bundled.selectors.contains(&"google".to_string()));
+    assert!(bundled.selectors.contains(&"selector1".to_string()));
+    assert!(!bundled.selectors.iter().any(|s| s.starts_with(COMMENT)));
+
+    let mut selector_list =
+        SelectorList::parse("# comment\nS1\n\ns1 # repeat\nbad selector\nmail.2021\n");
+    assert_eq!(selector_list.selectors, vec!["s1", "mail.2021"]);
+
+    selector_list.extend(vec!["k1".to_string(), "s1".to_string()]);
+    assert_eq!(selector_list.selectors, vec!["s1", "mail.2021", "k1"]);
+
+    assert_eq!(key_name("s1", "example.com."), "s1._domainkey.example.com");
+}
+
+#[test]
+fn dkim_parse_domain_list() {
+    let list = "example.com\n\nexample.org, s1 ,k1\nExample.org,s2,s2,bad selector\n";
+
+    assert_eq!(
+        parse_domain_list(list, true),
+        vec![
+            ("example.com".to_string(), vec![]),
+            (
+                "example.org".to_string(),
+                vec!["s1".to_string(), "k1".to_string()]
+            ),
+            ("Example.org".to_string(), vec!["s2".to_string()]),
+        ]
+    );
+
+    // Without selector fields a line is only ever a domain
+    assert_eq!(
+        parse_domain_list(list, false),
+        vec![
+            ("example.com".to_string(), vec![]),
+            ("example.org, s1 ,k1".to_string(), vec![]),
+            ("Example.org,s2,s2,bad selector".to_string(), vec![]),
+        ]
+    );
+}
+
+#[test]
+fn dkim_selector_discovery() {
+    let mut discovery = SelectorDiscovery::default();
+    assert!(discovery.visit(
+        "google",
+        &[txt_record(
+            "google._domainkey.example.com",
+            "v=DKIM1; k=rsa; p=MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEA",
+        )],
+    ));
+    assert!(!discovery.visit(
+        "s1",
+        &[txt_record("s1._domainkey.example.com", "v=spf1 -all")],
+    ));
+    assert!(!discovery.visit("k1", &[]));
+
+    assert_eq!(
+        discovery,
+        SelectorDiscovery {
+            probed: 3,
+            found: vec!["google".to_string()],
+        }
+    );
+}
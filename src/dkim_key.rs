@@ -0,0 +1,460 @@
+use crate::parse::join_txt_strings;
+use crate::tag_list::{DiagnosticKind, TagList};
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use std::fmt;
+use trust_dns_client::rr::Record;
+
+const DKIM1: &str = "DKIM1";
+
+const V_TAG: &str = "v";
+const K_TAG: &str = "k";
+const P_TAG: &str = "p";
+const T_TAG: &str = "t";
+const H_TAG: &str = "h";
+const S_TAG: &str = "s";
+
+const LIST_SEPARATOR: char = ':';
+
+const KEY_RSA: &str = "rsa";
+const KEY_ED25519: &str = "ed25519";
+const HASH_SHA256: &str = "sha256";
+const FLAG_TESTING: &str = "y";
+const SERVICE_EMAIL: &str = "email";
+const SERVICE_ANY: &str = "*";
+
+/// Verifiers must not accept smaller RSA keys and signers should use 2048 bits, RFC 8301 §3.2.
+const MIN_RSA_BITS: usize = 1024;
+const RECOMMENDED_RSA_BITS: usize = 2048;
+const ED25519_KEY_LEN: usize = 32;
+const ED25519_BITS: usize = 256;
+
+const DER_SEQUENCE: u8 = 0x30;
+const DER_INTEGER: u8 = 0x02;
+const DER_BIT_STRING: u8 = 0x03;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyType {
+    Rsa,
+    Ed25519,
+}
+
+impl fmt::Display for KeyType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Rsa => write!(f, "{}", KEY_RSA),
+            Self::Ed25519 => write!(f, "{}", KEY_ED25519),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DkimFinding {
+    NoRecord,
+    MultipleRecords(usize),
+    SyntaxError(String),
+    Revoked,
+    Testing,
+    WeakKey(usize),
+    ShortKey(usize),
+    UnsupportedKeyType(String),
+    UnsupportedHash(String),
+    NotForEmail(String),
+    BadPublicKey(String),
+}
+
+impl fmt::Display for DkimFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoRecord => write!(f, "No key record"),
+            Self::MultipleRecords(n) => write!(f, "{} key records", n),
+            Self::SyntaxError(s) => write!(f, "{}", s),
+            Self::Revoked => write!(f, "Empty p: Key revoked"),
+            Self::Testing => write!(f, "t=y: Testing mode, verifiers may ignore failures"),
+            Self::WeakKey(bits) => write!(
+                f,
+                "{}-bit RSA key: Verifiers reject keys under {} bits",
+                bits, MIN_RSA_BITS
+            ),
+            Self::ShortKey(bits) => write!(
+                f,
+                "{}-bit RSA key: Keys should be at least {} bits",
+                bits, RECOMMENDED_RSA_BITS
+            ),
+            Self::UnsupportedKeyType(k) => write!(f, "k={}: Unsupported key type", k),
+            Self::UnsupportedHash(h) => write!(f, "h={}: No supported hash algorithm", h),
+            Self::NotForEmail(s) => write!(f, "s={}: Key not usable for email", s),
+            Self::BadPublicKey(e) => write!(f, "Bad public key: {}", e),
+        }
+    }
+}
+
+impl DkimFinding {
+    /// Findings that leave the selector without a key verifiers accept.
+    pub fn is_invalid(&self) -> bool {
+        !matches!(self, Self::Revoked | Self::Testing | Self::ShortKey(_))
+    }
+}
+
+/// A finding along with the selector whose key it is about.
+pub struct SelectorFinding<'a> {
+    pub selector: &'a str,
+    pub finding: &'a DkimFinding,
+}
+
+impl<'a> fmt::Display for SelectorFinding<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.selector, self.finding)
+    }
+}
+
+/// The key record published for a selector and what is wrong with it.
+#[derive(Debug, PartialEq)]
+pub struct DkimKeyCheck {
+    pub selector: String,
+    pub key_type: Option<KeyType>,
+    pub key_bits: Option<usize>,
+    pub findings: Vec<DkimFinding>,
+}
+
+impl fmt::Display for DkimKeyCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.key_type, self.key_bits) {
+            (Some(key_type), Some(bits)) => write!(f, "{}: {} {}", self.selector, key_type, bits),
+            _ if self.findings.contains(&DkimFinding::Revoked) => {
+                write!(f, "{}: revoked", self.selector)
+            }
+            _ => write!(f, "{}: none", self.selector),
+        }
+    }
+}
+
+impl DkimKeyCheck {
+    /// Checks the TXT answers at `<selector>._domainkey.<domain>`.
+    pub fn from_answers(selector: &str, r: &[Record]) -> Self {
+        let txts: Vec<String> = r
+            .iter()
+            .filter_map(|record| record.rdata().as_txt())
+            .map(join_txt_strings)
+            .collect();
+
+        match txts.len() {
+            0 => Self::new(selector, vec![DkimFinding::NoRecord]),
+            1 => Self::from_txt(selector, &txts[0]),
+            n => Self::new(selector, vec![DkimFinding::MultipleRecords(n)]),
+        }
+    }
+
+    /// Parses a key record, see RFC 6376 §3.6.1.
+    pub fn from_txt(selector: &str, txt: &str) -> Self {
+        let tag_list = TagList::parse_with_base64_tags(txt, &[P_TAG]);
+        let mut check = Self::new(selector, Vec::new());
+
+        // An empty `p` is how a key is revoked rather than a syntax error
+        check.findings.extend(
+            tag_list
+                .diagnostics
+                .iter()
+                .filter(|d| d.kind != DiagnosticKind::EmptyValue(P_TAG.to_string()))
+                .map(|d| DkimFinding::SyntaxError(d.to_string())),
+        );
+
+        let tag = |name: &str| {
+            tag_list
+                .tags
+                .iter()
+                .find(|t| t.name == name)
+                .map(|t| t.value)
+        };
+
+        if let Some(v) = tag(V_TAG).filter(|v| *v != DKIM1) {
+            check.findings.push(DkimFinding::SyntaxError(format!(
+                "v={} is not {}",
+                v, DKIM1
+            )));
+        }
+
+        if let Some(h) = tag(H_TAG).filter(|h| !list(h).contains(&HASH_SHA256)) {
+            check
+                .findings
+                .push(DkimFinding::UnsupportedHash(h.to_string()));
+        }
+
+        if let Some(s) = tag(S_TAG).filter(|s| {
+            !list(s)
+                .iter()
+                .any(|s| *s == SERVICE_EMAIL || *s == SERVICE_ANY)
+        }) {
+            check.findings.push(DkimFinding::NotForEmail(s.to_string()));
+        }
+
+        if tag(T_TAG).is_some_and(|t| list(t).contains(&FLAG_TESTING)) {
+            check.findings.push(DkimFinding::Testing);
+        }
+
+        let key_type = match tag(K_TAG).unwrap_or(KEY_RSA) {
+            KEY_RSA => KeyType::Rsa,
+            KEY_ED25519 => KeyType::Ed25519,
+            k => {
+                check
+                    .findings
+                    .push(DkimFinding::UnsupportedKeyType(k.to_string()));
+                return check;
+            }
+        };
+
+        let p: String = match tag(P_TAG) {
+            Some(p) => p.chars().filter(|c| !c.is_whitespace()).collect(),
+            None => {
+                check
+                    .findings
+                    .push(DkimFinding::SyntaxError(format!("Missing `{}` tag", P_TAG)));
+                return check;
+            }
+        };
+
+        if p.is_empty() {
+            check.findings.push(DkimFinding::Revoked);
+            return check;
+        }
+
+        let key = match base64_decode(&p) {
+            Some(key) => key,
+            None => {
+                check
+                    .findings
+                    .push(DkimFinding::BadPublicKey("Not base64".to_string()));
+                return check;
+            }
+        };
+
+        let key_bits = match key_type {
+            KeyType::Rsa => rsa_modulus_bits(&key),
+            KeyType::Ed25519 if key.len() == ED25519_KEY_LEN => Some(ED25519_BITS),
+            KeyType::Ed25519 => None,
+        };
+
+        match (key_type, key_bits) {
+            (KeyType::Rsa, Some(bits)) if bits < MIN_RSA_BITS => {
+                check.findings.push(DkimFinding::WeakKey(bits))
+            }
+            (KeyType::Rsa, Some(bits)) if bits < RECOMMENDED_RSA_BITS => {
+                check.findings.push(DkimFinding::ShortKey(bits))
+            }
+            (_, Some(_)) => (),
+            (_, None) => check.findings.push(DkimFinding::BadPublicKey(format!(
+                "Not an {} key",
+                key_type
+            ))),
+        }
+
+        check.key_type = Some(key_type);
+        check.key_bits = key_bits;
+        check
+    }
+
+    fn new(selector: &str, findings: Vec<DkimFinding>) -> Self {
+        Self {
+            selector: selector.to_string(),
+            key_type: None,
+            key_bits: None,
+            findings,
+        }
+    }
+}
+
+/// Values of a colon separated tag such as `h=sha1:sha256`.
+fn list(value: &str) -> Vec<&str> {
+    value.split(LIST_SEPARATOR).map(|v| v.trim()).collect()
+}
+
+fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.trim_end_matches('=');
+    let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for c in encoded.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(decoded)
+}
+
+/// Reads one DER element, returning its tag, contents and the bytes after it.
+fn der_element(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = der.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+
+    let (len, rest) = match first {
+        0..=0x7f => (usize::from(first), rest),
+        // Long form, the low bits give the number of length bytes that follow
+        _ => {
+            let n = usize::from(first & 0x7f);
+            if n == 0 || n > std::mem::size_of::<usize>() || rest.len() < n {
+                return None;
+            }
+
+            let len = rest[..n]
+                .iter()
+                .fold(0, |len, b| (len << 8) | usize::from(*b));
+            (len, &rest[n..])
+        }
+    };
+
+    match rest.len() >= len {
+        true => Some((tag, &rest[..len], &rest[len..])),
+        false => None,
+    }
+}
+
+/// Size of the modulus of a DER `SubjectPublicKeyInfo` or bare `RSAPublicKey`.
+fn rsa_modulus_bits(der: &[u8]) -> Option<usize> {
+    let (tag, contents, _) = der_element(der)?;
+    if tag != DER_SEQUENCE {
+        return None;
+    }
+
+    let (tag, _, rest) = der_element(contents)?;
+
+    let rsa_public_key = match tag {
+        DER_INTEGER => contents,
+        // SubjectPublicKeyInfo wraps the key in a bit string after the algorithm
+        DER_SEQUENCE => {
+            let (tag, bit_string, _) = der_element(rest)?;
+            if tag != DER_BIT_STRING || bit_string.is_empty() {
+                return None;
+            }
+
+            let (tag, contents, _) = der_element(&bit_string[1..])?;
+            if tag != DER_SEQUENCE {
+                return None;
+            }
+            contents
+        }
+        _ => return None,
+    };
+
+    let (tag, modulus, _) = der_element(rsa_public_key)?;
+    if tag != DER_INTEGER {
+        return None;
+    }
+
+    let modulus = match modulus.iter().position(|b| *b != 0) {
+        Some(i) => &modulus[i..],
+        None => return None,
+    };
+
+    Some(modulus.len() * 8 - modulus[0].leading_zeros() as usize)
+}
+
+// Tests
+#[cfg(test)]
+const RSA_512: &str = "MFwwDQYJKoZIhvcNAQEBBQADSwAwSAJBAK0x/SeRM+9k+jQV317mDVHt9gqkE3ClGs6EcdGjnFTTcLIpCGwSngIZXKLUbW/ofKu/31OK5scHZdnAPuwESl0CAwEAAQ==";
+#[cfg(test)]
+const RSA_1024: &str = "MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQCisqbqbB3O3zbI/EnjCUTGQeDFmsr9GH2T9b9KRQ6tuiy2WUE9qTAnbWevORRL77mAA4doRcOJG8GgFlXTzINflTdJ2H42EOP7l+h+znuhEybJIg5zFRfOpQ/a4ztD9Zm+IpA9tUvWwvdCW9N+oYcxRddHERoz/ZaEEOaTkBskOwIDAQAB";
+#[cfg(test)]
+const RSA_2048: &str = "MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAwkoOzjQNbs+n+ZcIAtR08BpFU0feNFc9+x4pnE2rig6hCfwqNDFV29QmOhI0UEIzBhwmNpgG6W1I8cFiWeNOSUsF7DYIjTEAILcPFdMfR4yvyRQgeef0YoMQIra7xaVRDZrw5YV0KrQsNwIrQ8Dq5W9YE+nmwrR3rWZMxSRkdsuVNhco+lcmoGuDTIxrMuEEaFBSEKoPRikGCs3o0DJXXiGsx53WCp3EsRlxCJ3pwmNWJnuTUdAJE+jKP/GOeByuIjte2AQe9+Lm01JDGtWQunL/vwNt+zOy2LCod0/bMrsQKD8JXgAm6PylxTXwBYZNenPNuI3ndy/D7vGbHoa/LwIDAQAB";
+
+/// 2048-bit key with public exponent 3, whose encoding ends in `=` padding.
+#[cfg(test)]
+const RSA_2048_PADDED: &str = "MIIBIDANBgkqhkiG9w0BAQEFAAOCAQ0AMIIBCAKCAQEAvhsWww6nGDV2ETK0AMnn21TKkl3bfcTfTmBXjkPRqBNQNAwWrSQ0MI990N2oT0zseM1WH4XavTlQuOpYDXgLr5xnQYtK/0B9YDNRUgCJmTAVHy1ijPLgL5evBF+ChS98TVUGxr9A6a3+PuiOGv9qnL1KlRYr5I1NLPG+Xp/G+0IuXh1X0egEa0f0lKXb+mc/IL3WalRpAFHMvyN9s/b2W3ASuDrl0/vASuJISdkAF7Sb3XJTeeQk7j5hFxh0c4DUkSncXNROMeGY1aphRIaLcSLkEaJjtkyFcAPRqX+pBGwOB0656m0WSq47xTSt9YUnorwPW5J7TmB04kWOOoQ7BQIBAw==";
+
+#[test]
+fn dkim_key_strength() {
+    let check = DkimKeyCheck::from_txt("s1", &format!("v=DKIM1; k=rsa; p={}", RSA_2048));
+    assert_eq!(check.findings, vec![]);
+    assert_eq!(check.to_string(), "s1: rsa 2048");
+
+    // Keys split over several strings may have whitespace inside
+    let (head, tail) = RSA_1024.split_at(100);
+    let check = DkimKeyCheck::from_txt("s1", &format!("p={} {}", head, tail));
+    assert_eq!(check.findings, vec![DkimFinding::ShortKey(1024)]);
+
+    // Padding after whitespace is not the start of another tag
+    let (head, rest) = RSA_2048_PADDED.split_at(200);
+    let (middle, tail) = rest.split_at(rest.len() - 20);
+    let check = DkimKeyCheck::from_txt(
+        "s1",
+        &format!("v=DKIM1; k=rsa; p={} {}\t{}", head, middle, tail),
+    );
+    assert_eq!(check.findings, vec![]);
+    assert_eq!(check.key_bits, Some(2048));
+
+    let check = DkimKeyCheck::from_txt("s1", &format!("v=DKIM1; p={}", RSA_512));
+    assert_eq!(check.findings, vec![DkimFinding::WeakKey(512)]);
+    assert!(check.findings[0].is_invalid());
+
+    // Bare RSAPublicKey rather than SubjectPublicKeyInfo
+    let check = DkimKeyCheck::from_txt(
+        "s1",
+        "p=MEgCQQCtMf0nkTPvZPo0Fd9e5g1R7fYKpBNwpRrOhHHRo5xU03CyKQhsEp4CGVyi1G1v6Hyrv99TiubHB2XZwD7sBEpdAgMBAAE=",
+    );
+    assert_eq!(check.key_bits, Some(512));
+
+    let check = DkimKeyCheck::from_txt(
+        "ed",
+        "v=DKIM1; k=ed25519; p=8Di0IWmXhRIsK4HiJ3+k9togvYvCK/rkBc7mKN+fqhc=",
+    );
+    assert_eq!(check.findings, vec![]);
+    assert_eq!(check.to_string(), "ed: ed25519 256");
+
+    let check = DkimKeyCheck::from_txt("ed", &format!("k=ed25519; p={}", RSA_512));
+    assert_eq!(
+        check.findings,
+        vec![DkimFinding::BadPublicKey("Not an ed25519 key".to_string())]
+    );
+}
+
+#[test]
+fn dkim_key_findings() {
+    let check = DkimKeyCheck::from_txt("old", "v=DKIM1; k=rsa; p=");
+    assert_eq!(check.findings, vec![DkimFinding::Revoked]);
+    assert_eq!(check.to_string(), "old: revoked");
+
+    let check = DkimKeyCheck::from_txt(
+        "s1",
+        &format!("v=DKIM2; h=sha1; s=tlsrpt; t=y:s; p={}", RSA_2048),
+    );
+    assert_eq!(
+        check.findings,
+        vec![
+            DkimFinding::SyntaxError("v=DKIM2 is not DKIM1".to_string()),
+            DkimFinding::UnsupportedHash("sha1".to_string()),
+            DkimFinding::NotForEmail("tlsrpt".to_string()),
+            DkimFinding::Testing,
+        ]
+    );
+
+    assert_eq!(
+        DkimKeyCheck::from_txt("s1", "k=dsa; p=AAAA").findings,
+        vec![DkimFinding::UnsupportedKeyType("dsa".to_string())]
+    );
+    assert_eq!(
+        DkimKeyCheck::from_txt("s1", "v=DKIM1; k=rsa").findings,
+        vec![DkimFinding::SyntaxError("Missing `p` tag".to_string())]
+    );
+    assert_eq!(
+        DkimKeyCheck::from_txt("s1", "p=not*base64").findings,
+        vec![DkimFinding::BadPublicKey("Not base64".to_string())]
+    );
+    assert_eq!(
+        DkimKeyCheck::from_answers("s1", &[]).findings,
+        vec![DkimFinding::NoRecord]
+    );
+}
@@ -1,17 +1,53 @@
 use csv_async::AsyncSerializer;
+use std::sync::Arc;
 use tokio::fs::File;
 use tokio::sync::mpsc;
-use trust_dns_client::rr::Record;
 
-mod parse;
-mod query;
 #[macro_use]
 mod utils;
+mod bimi;
+mod check_host;
+mod cname;
+mod discovery;
+mod dkim;
+mod dkim_key;
+mod mta_sts;
+mod mx;
+mod parked;
+mod parse;
+mod psl;
+mod query;
+mod resolver;
+mod score;
+mod spf;
+mod spf_expansion;
+mod spoofability;
+mod suggest;
+mod tag_list;
+mod tls_rpt;
+mod uri;
 
 #[tokio::main]
 async fn main() -> Result<(), ()> {
     let config = utils::Config::new();
 
+    // Evaluate SPF for a single message instead of checking a list of domains
+    if let Some(args) = config.check_host.clone() {
+        let ip = args[0]
+            .parse()
+            .map_err(|e| print_err!("Failed to parse sender IP: {} - {}", args[0], e))?;
+
+        let spf_verdict = tokio::task::spawn_blocking(move || {
+            let helo = args.get(2).map_or("", String::as_str);
+            check_host::check_host(&query::DnsResolver, ip, &args[1], helo)
+        })
+        .await
+        .map_err(|e| print_err!("Failed to evaluate SPF - {}", e))?;
+
+        println!("{}", spf_verdict);
+        return Ok(());
+    }
+
     // Open asynchronous file writers and serializers
     let output_dmarc_file = File::create(&config.output_dmarc_file).await.map_err(|e| {
         print_err!(
@@ -23,6 +59,72 @@ async fn main() -> Result<(), ()> {
 
     let mut output_dmarc_filewriter = csv_async::AsyncSerializer::from_writer(output_dmarc_file);
     
+    // Load the public suffix list used to find organizational domains
+    let public_suffix_list = match &config.public_suffix_list {
+        Some(path) => psl::PublicSuffixList::parse(&std::fs::read_to_string(path).map_err(|e| {
+            print_err!("Failed to read public suffix list: {} - {}", path, e)
+        })?),
+        None => psl::PublicSuffixList::bundled(),
+    };
+
+    // Weights used to score and grade each domain
+    let score_weights = match &config.score_weights {
+        Some(weights) => score::ScoreWeights::parse(weights)
+            .map_err(|e| print_err!("Failed to parse score weights: {} - {}", weights, e))?,
+        None => score::ScoreWeights::default(),
+    };
+
+    // DKIM selectors probed for each domain, when discovery is enabled
+    let dkim_selectors = match &config.dkim_selector_list {
+        Some(path) => {
+            let listed = dkim::SelectorList::parse(&std::fs::read_to_string(path).map_err(|e| {
+                print_err!("Failed to read DKIM selector list: {} - {}", path, e)
+            })?);
+
+            let mut selector_list = match config.replace_dkim_selectors {
+                true => dkim::SelectorList::default(),
+                false => dkim::SelectorList::bundled(),
+            };
+            selector_list.extend(listed.selectors);
+            Some(selector_list)
+        }
+        None if config.dkim_discovery => Some(dkim::SelectorList::bundled()),
+        None => None,
+    };
+
+    // Fetcher for the MTA-STS policies, when the check is enabled
+    let mta_sts_fetcher = if config.mta_sts || config.mta_sts_base_url.is_some() {
+        let ca_cert = match &config.mta_sts_ca_cert {
+            Some(path) => Some(std::fs::read(path).map_err(|e| {
+                print_err!("Failed to read MTA-STS CA certificate: {} - {}", path, e)
+            })?),
+            None => None,
+        };
+
+        let base_url = config
+            .mta_sts_base_url
+            .as_deref()
+            .unwrap_or(mta_sts::DEFAULT_POLICY_BASE_URL);
+
+        Some(mta_sts::PolicyFetcher::new(base_url, ca_cert.as_deref())
+            .map_err(|e| print_err!("Failed to set up MTA-STS policy fetching - {}", e))?)
+    } else {
+        None
+    };
+
+    let query_options = Arc::new(query::QueryOptions {
+        public_suffix_list,
+        tree_walk: config.tree_walk,
+        dmarcbis: config.dmarcbis,
+        parked: config.parked,
+        spf_expansion: config.spf_expansion,
+        score_weights,
+        dkim_selectors,
+        mta_sts_fetcher,
+        tls_rpt: config.tls_rpt,
+        bimi: config.bimi,
+    });
+
     // Read all domains for batching
     let domain_names = std::fs::read_to_string(&config.input_domain_file)
         .map_err(|e| {
@@ -35,22 +137,24 @@ async fn main() -> Result<(), ()> {
         })?;
     
     // Batch into iter chunks of batch_size
-    for domain_chunk in domain_names
-        .lines()
-        .collect::<Vec<&str>>()
+    for domain_chunk in dkim::parse_domain_list(&domain_names, config.dkim_selector_fields)
         .chunks(config.batch_size) {
 
         let (tx, rx) = mpsc::channel(query::DNS_SERVERS.len());
 
-        for domain_name in domain_chunk {
+        for (domain_name, dkim_selectors) in domain_chunk {
             
-            // Convert domain name to string for tokio task
-            let domain_name = domain_name.to_string();
+            // Clone domain name and selectors for tokio task
+            let domain_name = domain_name.clone();
+            let dkim_selectors = dkim_selectors.clone();
 
-            // Clone Sender tx for task move
+            // Clone Sender tx and query options for task move
             let tx = tx.clone();
+            let query_options = query_options.clone();
 
-            tokio::spawn(async move { query::try_query(domain_name, tx).await });
+            tokio::spawn(async move {
+                query::try_query(domain_name, dkim_selectors, query_options, tx).await
+            });
         }
         // Close original Sender tx to prevent a dead lock
         drop(tx);
@@ -76,62 +180,22 @@ async fn main() -> Result<(), ()> {
 
 async fn write_dmarc_output_to_csv(
     mut output_dmarc_filewriter: AsyncSerializer<File>,
-    mut rx: mpsc::Receiver<(String, Vec<Record>)>,
+    mut rx: mpsc::Receiver<(String, parse::Dmarc)>,
 ) -> Result<AsyncSerializer<File>, String> {
     // Recieve data from channel asynchronously
-    while let Some((domain_name, dns_respoonse_answers)) = rx.recv().await {
+    while let Some((domain_name, dmarc)) = rx.recv().await {
         println!("Scanned '{}'", &domain_name);
 
-        let string_records = parse::StringRecords::new(&dns_respoonse_answers);
-
-        match string_records {
-            Some(sr) => match sr {
-                // Write single DMARC record
-                parse::StringRecords::Single(s) => {
-                    let dmarc = parse::Dmarc::new(&domain_name, Some(s));
-                    output_dmarc_filewriter
-                        .serialize::<parse::Dmarc>(dmarc)
-                        .await
-                        .map_err(|e| {
-                            fmt_err!(
-                                "Failed to write single record for domain name: {} - {}",
-                                domain_name,
-                                e
-                            )
-                        })?
-                }
-                // Write multiple DMARC record
-                parse::StringRecords::Multiple(vs) => {
-                    for s in vs {
-                        let dmarc = parse::Dmarc::new(&domain_name, Some(s));
-                        output_dmarc_filewriter
-                            .serialize::<parse::Dmarc>(dmarc)
-                            .await
-                            .map_err(|e| {
-                                fmt_err!(
-                                    "Failed to write multiple record for domain name: {} - {}",
-                                    domain_name,
-                                    e
-                                )
-                            })?
-                    }
-                }
-            },
-            // No DMARC record
-            None => {
-                let dmarc = parse::Dmarc::new(&domain_name, None);
-                output_dmarc_filewriter
-                    .serialize::<parse::Dmarc>(dmarc)
-                    .await
-                    .map_err(|e| {
-                        fmt_err!(
-                            "Failed to write empty record for domain name: {} - {}",
-                            domain_name,
-                            e
-                        )
-                    })?
-            }
-        }
+        output_dmarc_filewriter
+            .serialize::<parse::Dmarc>(dmarc)
+            .await
+            .map_err(|e| {
+                fmt_err!(
+                    "Failed to write record for domain name: {} - {}",
+                    domain_name,
+                    e
+                )
+            })?
     }
 
     Ok(output_dmarc_filewriter)
@@ -0,0 +1,592 @@
+use crate::mx::MxCheck;
+use crate::parse::join_txt_strings;
+use crate::tag_list::TagList;
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use std::fmt;
+use std::time::Duration;
+use trust_dns_client::rr::Record;
+
+pub const MTA_STS_LABEL: &str = "_mta-sts";
+
+/// Where policies are served, `{domain}` is replaced by the domain being checked.
+pub const DEFAULT_POLICY_BASE_URL: &str = "https://mta-sts.{domain}";
+const DOMAIN_PLACEHOLDER: &str = "{domain}";
+const POLICY_PATH: &str = "/.well-known/mta-sts.txt";
+const POLICY_CONTENT_TYPE: &str = "text/plain";
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Senders may refuse larger policies, RFC 8461 §3.3.
+const MAX_POLICY_LEN: usize = 64 * 1024;
+
+const STSV1: &str = "STSv1";
+const V_TAG: &str = "v";
+const ID_TAG: &str = "id";
+const MAX_ID_LEN: usize = 32;
+
+const KEY_SEPARATOR: char = ':';
+const VERSION_KEY: &str = "version";
+const MODE_KEY: &str = "mode";
+const MX_KEY: &str = "mx";
+const MAX_AGE_KEY: &str = "max_age";
+
+const MODE_ENFORCE: &str = "enforce";
+const MODE_TESTING: &str = "testing";
+const MODE_NONE: &str = "none";
+
+const MAX_MAX_AGE: u32 = 31_557_600;
+const MIN_RECOMMENDED_MAX_AGE: u32 = 86_400;
+
+const WILDCARD: &str = "*.";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Enforce,
+    Testing,
+    None,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Enforce => write!(f, "{}", MODE_ENFORCE),
+            Self::Testing => write!(f, "{}", MODE_TESTING),
+            Self::None => write!(f, "{}", MODE_NONE),
+        }
+    }
+}
+
+/// A policy served at `https://mta-sts.<domain>/.well-known/mta-sts.txt`, RFC 8461 §3.2.
+#[derive(Debug, Default, PartialEq)]
+pub struct MtaStsPolicy {
+    pub mode: Option<Mode>,
+    pub mx: Vec<String>,
+    pub max_age: Option<u32>,
+    pub errors: Vec<String>,
+}
+
+impl MtaStsPolicy {
+    /// Parses the `key: value` lines of a policy, unknown keys are ignored.
+    pub fn parse(body: &str) -> Self {
+        let mut policy = Self::default();
+        let mut version = None;
+        let mut keys_seen: Vec<&str> = Vec::new();
+
+        for line in body.lines().map(|l| l.trim_end_matches('\r')) {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (key, value) = match line.split_once(KEY_SEPARATOR) {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => {
+                    policy.errors.push(format!("Missing `:` in `{}`", line));
+                    continue;
+                }
+            };
+
+            keys_seen.push(key);
+
+            match key {
+                VERSION_KEY => version = Some(value.to_string()),
+                MODE_KEY => {
+                    policy.mode = match value {
+                        MODE_ENFORCE => Some(Mode::Enforce),
+                        MODE_TESTING => Some(Mode::Testing),
+                        MODE_NONE => Some(Mode::None),
+                        _ => {
+                            policy.errors.push(format!("Unknown mode `{}`", value));
+                            None
+                        }
+                    }
+                }
+                MX_KEY => policy.mx.push(value.to_lowercase()),
+                MAX_AGE_KEY => match value.parse::<u32>() {
+                    Ok(max_age) if max_age <= MAX_MAX_AGE => policy.max_age = Some(max_age),
+                    _ => policy.errors.push(format!(
+                        "max_age `{}` is not between 0 and {}",
+                        value, MAX_MAX_AGE
+                    )),
+                },
+                _ => (),
+            }
+        }
+
+        match version {
+            Some(v) if v == STSV1 => (),
+            Some(v) => policy
+                .errors
+                .push(format!("version `{}` is not {}", v, STSV1)),
+            None => policy.errors.push(format!("Missing `{}`", VERSION_KEY)),
+        }
+
+        for key in &[MODE_KEY, MAX_AGE_KEY] {
+            if !keys_seen.contains(key) {
+                policy.errors.push(format!("Missing `{}`", key));
+            }
+        }
+
+        // Only a withdrawn policy may list no MX host
+        match policy.mode {
+            Some(mode) if mode != Mode::None && policy.mx.is_empty() => policy
+                .errors
+                .push(format!("Missing `{}` in {} mode", MX_KEY, mode)),
+            _ => (),
+        }
+
+        policy
+    }
+}
+
+/// Whether `host` matches a policy `mx` pattern, a leading `*.` matches one label.
+pub fn mx_pattern_matches(pattern: &str, host: &str) -> bool {
+    let pattern = normalize(pattern);
+    let host = normalize(host);
+
+    match pattern.strip_prefix(WILDCARD) {
+        Some(parent) => host
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && rest == parent),
+        None => host == pattern,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MtaStsFinding {
+    NoRecord,
+    MultipleRecords(usize),
+    RecordSyntax(String),
+    PolicyFetch(String),
+    PolicySyntax(String),
+    ModeTesting,
+    ModeNone,
+    ShortMaxAge(u32),
+    UnmatchedMx(String),
+    UnusedPattern(String),
+}
+
+impl fmt::Display for MtaStsFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoRecord => write!(f, "No MTA-STS record: Senders may deliver without TLS"),
+            Self::MultipleRecords(n) => write!(f, "{} MTA-STS records: Senders ignore them", n),
+            Self::RecordSyntax(s) => write!(f, "Record: {}", s),
+            Self::PolicyFetch(e) => write!(f, "Policy not fetched: {}", e),
+            Self::PolicySyntax(s) => write!(f, "Policy: {}", s),
+            Self::ModeTesting => write!(f, "mode testing: TLS failures are only reported"),
+            Self::ModeNone => write!(f, "mode none: Policy withdrawn"),
+            Self::ShortMaxAge(n) => {
+                write!(f, "max_age {}: Senders forget the policy within a day", n)
+            }
+            Self::UnmatchedMx(host) => write!(
+                f,
+                "MX `{}` not matched by the policy, enforcing senders will not deliver to it",
+                host
+            ),
+            Self::UnusedPattern(pattern) => write!(f, "mx `{}` matches no MX host", pattern),
+        }
+    }
+}
+
+impl MtaStsFinding {
+    /// Findings that leave senders without a usable policy or unable to deliver.
+    pub fn is_invalid(&self) -> bool {
+        matches!(
+            self,
+            Self::MultipleRecords(_)
+                | Self::RecordSyntax(_)
+                | Self::PolicyFetch(_)
+                | Self::PolicySyntax(_)
+                | Self::UnmatchedMx(_)
+        )
+    }
+}
+
+/// The MTA-STS record and policy of a domain and what is wrong with them.
+#[derive(Debug, Default, PartialEq)]
+pub struct MtaStsCheck {
+    pub id: Option<String>,
+    pub policy: Option<MtaStsPolicy>,
+    pub findings: Vec<MtaStsFinding>,
+}
+
+impl MtaStsCheck {
+    /// Selects the record from the TXT answers at `_mta-sts.<domain>`, RFC 8461 §3.1.
+    pub fn from_answers(r: &[Record]) -> Self {
+        let sts_txts: Vec<String> = r
+            .iter()
+            .filter_map(|record| record.rdata().as_txt())
+            .map(join_txt_strings)
+            .filter(|txt| txt.starts_with(&format!("{}={}", V_TAG, STSV1)))
+            .collect();
+
+        match sts_txts.len() {
+            0 => Self {
+                findings: vec![MtaStsFinding::NoRecord],
+                ..Self::default()
+            },
+            1 => Self::from_txt(&sts_txts[0]),
+            n => Self {
+                findings: vec![MtaStsFinding::MultipleRecords(n)],
+                ..Self::default()
+            },
+        }
+    }
+
+    pub fn from_txt(txt: &str) -> Self {
+        let mut tag_list = TagList::parse(txt);
+        tag_list.check_version_first(V_TAG);
+
+        let mut findings: Vec<MtaStsFinding> = tag_list
+            .diagnostics
+            .iter()
+            .map(|d| MtaStsFinding::RecordSyntax(d.to_string()))
+            .collect();
+
+        let id = tag_list
+            .tags
+            .iter()
+            .find(|t| t.name == ID_TAG)
+            .map(|t| t.value.to_string());
+
+        match &id {
+            Some(id)
+                if !id.is_empty()
+                    && id.len() <= MAX_ID_LEN
+                    && id.chars().all(|c| c.is_ascii_alphanumeric()) => {}
+            Some(id) => findings.push(MtaStsFinding::RecordSyntax(format!(
+                "id `{}` is not 1 to {} letters and digits",
+                id, MAX_ID_LEN
+            ))),
+            None => findings.push(MtaStsFinding::RecordSyntax(format!("Missing `{}`", ID_TAG))),
+        }
+
+        Self {
+            id,
+            policy: None,
+            findings,
+        }
+    }
+
+    /// Whether a usable record tells senders to fetch the policy.
+    pub fn has_record(&self) -> bool {
+        self.id.is_some() && !self.findings.iter().any(|f| f.is_invalid())
+    }
+
+    /// Checks the fetched policy and its `mx` patterns against the MX hosts of the domain.
+    pub fn check_policy(&mut self, fetched: Result<String, String>, mx_check: &MxCheck) {
+        let policy = match fetched {
+            Ok(body) => MtaStsPolicy::parse(&body),
+            Err(e) => {
+                self.findings.push(MtaStsFinding::PolicyFetch(e));
+                return;
+            }
+        };
+
+        self.findings.extend(
+            policy
+                .errors
+                .iter()
+                .map(|e| MtaStsFinding::PolicySyntax(e.clone())),
+        );
+
+        match policy.mode {
+            Some(Mode::Testing) => self.findings.push(MtaStsFinding::ModeTesting),
+            Some(Mode::None) => self.findings.push(MtaStsFinding::ModeNone),
+            _ => (),
+        }
+
+        if let Some(max_age) = policy.max_age.filter(|n| *n < MIN_RECOMMENDED_MAX_AGE) {
+            self.findings.push(MtaStsFinding::ShortMaxAge(max_age));
+        }
+
+        if policy.mode != Some(Mode::None) {
+            let hosts: Vec<&String> = mx_check
+                .exchanges
+                .iter()
+                .map(|(_, exchange)| exchange)
+                .filter(|exchange| !normalize(exchange).is_empty())
+                .collect();
+
+            for host in &hosts {
+                if !policy.mx.iter().any(|p| mx_pattern_matches(p, host)) {
+                    self.findings
+                        .push(MtaStsFinding::UnmatchedMx(normalize(host)));
+                }
+            }
+
+            for pattern in &policy.mx {
+                if !hosts.iter().any(|host| mx_pattern_matches(pattern, host)) {
+                    self.findings
+                        .push(MtaStsFinding::UnusedPattern(pattern.clone()));
+                }
+            }
+        }
+
+        self.policy = Some(policy);
+    }
+}
+
+/// Fetches MTA-STS policies, from the policy host of each domain unless a different base
+/// URL such as a local stand-in is configured.
+pub struct PolicyFetcher {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl PolicyFetcher {
+    /// `ca_cert` is an extra PEM root certificate to trust, e.g. that of a local stand-in.
+    pub fn new(base_url: &str, ca_cert: Option<&[u8]>) -> Result<Self, String> {
+        // Senders must not follow redirects, RFC 8461 §3.3
+        let mut builder = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(FETCH_TIMEOUT);
+
+        if let Some(ca_cert) = ca_cert {
+            let certificate = reqwest::Certificate::from_pem(ca_cert).map_err(|e| e.to_string())?;
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        Ok(Self {
+            client: builder.build().map_err(|e| e.to_string())?,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    pub fn policy_url(&self, domain_name: &str) -> String {
+        format!(
+            "{}{}",
+            self.base_url
+                .replace(DOMAIN_PLACEHOLDER, &normalize(domain_name)),
+            POLICY_PATH
+        )
+    }
+
+    /// Fetches the policy, reading no more than `MAX_POLICY_LEN` bytes of the body.
+    pub async fn fetch(&self, domain_name: &str) -> Result<String, String> {
+        let mut response = self
+            .client
+            .get(self.policy_url(domain_name))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if response.status() != reqwest::StatusCode::OK {
+            return Err(format!("HTTP status {}", response.status()));
+        }
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        if !content_type.starts_with(POLICY_CONTENT_TYPE) {
+            return Err(format!(
+                "Content type `{}` is not {}",
+                content_type, POLICY_CONTENT_TYPE
+            ));
+        }
+
+        let too_large = || format!("Policy larger than {} bytes", MAX_POLICY_LEN);
+
+        if response
+            .content_length()
+            .is_some_and(|len| len > MAX_POLICY_LEN as u64)
+        {
+            return Err(too_large());
+        }
+
+        // The length header may be missing or wrong, so the limit is also kept while reading
+        let mut body: Vec<u8> = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+            if body.len() + chunk.len() > MAX_POLICY_LEN {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        String::from_utf8(body).map_err(|e| e.to_string())
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_lowercase()
+}
+
+// Tests
+#[test]
+fn mta_sts_record() {
+    let check = MtaStsCheck::from_txt("v=STSv1; id=20230601T000000;");
+    assert_eq!(check.id, Some("20230601T000000".to_string()));
+    assert_eq!(check.findings, vec![]);
+    assert!(check.has_record());
+
+    let check = MtaStsCheck::from_txt("v=STSv1; id=2023-06-01");
+    assert_eq!(
+        check.findings,
+        vec![MtaStsFinding::RecordSyntax(
+            "id `2023-06-01` is not 1 to 32 letters and digits".to_string()
+        )]
+    );
+    assert!(!check.has_record());
+
+    assert_eq!(
+        MtaStsCheck::from_answers(&[]).findings,
+        vec![MtaStsFinding::NoRecord]
+    );
+}
+
+#[test]
+fn mta_sts_policy() {
+    let policy = MtaStsPolicy::parse(
+        "version: STSv1\r\nmode: enforce\r\nmx: mail.example.com\r\nmx: *.Example.net\r\nmax_age: 604800\r\n",
+    );
+    assert_eq!(
+        policy,
+        MtaStsPolicy {
+            mode: Some(Mode::Enforce),
+            mx: vec!["mail.example.com".to_string(), "*.example.net".to_string()],
+            max_age: Some(604800),
+            errors: vec![],
+        }
+    );
+
+    assert_eq!(
+        MtaStsPolicy::parse("version: STSv2\nmode: strict\nmax_age: -1\n").errors,
+        vec![
+            "Unknown mode `strict`",
+            "max_age `-1` is not between 0 and 31557600",
+            "version `STSv2` is not STSv1",
+        ]
+    );
+    assert_eq!(
+        MtaStsPolicy::parse("version: STSv1\nmode: testing\nmax_age: 86400").errors,
+        vec!["Missing `mx` in testing mode"]
+    );
+
+    assert!(mx_pattern_matches("*.example.net", "mx1.example.net."));
+    assert!(!mx_pattern_matches("*.example.net", "a.mx1.example.net"));
+    assert!(!mx_pattern_matches("*.example.net", "example.net"));
+    assert!(mx_pattern_matches("mail.example.com", "Mail.Example.com."));
+}
+
+#[test]
+fn mta_sts_check_policy() {
+    let mx_check = MxCheck {
+        exchanges: vec![
+            (10, "mx1.example.net.".to_string()),
+            (20, "backup.example.org.".to_string()),
+        ],
+        null_mx: false,
+        implicit_mx: false,
+        findings: vec![],
+    };
+
+    let mut check = MtaStsCheck::from_txt("v=STSv1; id=1");
+    check.check_policy(
+        Ok("version: STSv1\nmode: testing\nmx: *.example.net\nmx: mail.example.com\nmax_age: 3600\n"
+            .to_string()),
+        &mx_check,
+    );
+    assert_eq!(
+        check.findings,
+        vec![
+            MtaStsFinding::ModeTesting,
+            MtaStsFinding::ShortMaxAge(3600),
+            MtaStsFinding::UnmatchedMx("backup.example.org".to_string()),
+            MtaStsFinding::UnusedPattern("mail.example.com".to_string()),
+        ]
+    );
+
+    let mut check = MtaStsCheck::from_txt("v=STSv1; id=1");
+    check.check_policy(Err("HTTP status 404 Not Found".to_string()), &mx_check);
+    assert_eq!(
+        check.findings,
+        vec![MtaStsFinding::PolicyFetch(
+            "HTTP status 404 Not Found".to_string()
+        )]
+    );
+}
+
+#[tokio::test]
+async fn mta_sts_fetch_from_stand_in() {
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+
+    const CA_CERT: &[u8] = include_bytes!("../data/test/stand_in_ca.pem");
+    const CERT: &[u8] = include_bytes!("../data/test/stand_in_cert.pem");
+    const KEY: &[u8] = include_bytes!("../data/test/stand_in_key.pem");
+
+    let certs = rustls_pemfile::certs(&mut &CERT[..])
+        .unwrap()
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    let key = PrivateKey(rustls_pemfile::pkcs8_private_keys(&mut &KEY[..]).unwrap()[0].clone());
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .unwrap();
+    let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let policy = "version: STSv1\r\nmode: enforce\r\nmx: mx.example.com\r\nmax_age: 86400\r\n";
+    let oversized = "x".repeat(MAX_POLICY_LEN + 1);
+    let responses = vec![
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            policy.len(),
+            policy
+        ),
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            oversized.len()
+        ),
+        // No length header, the body runs until the connection closes
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\n{}",
+            oversized
+        ),
+    ];
+
+    tokio::spawn(async move {
+        for response in responses {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = acceptor.accept(stream).await.unwrap();
+            let mut request = [0; 1024];
+            let n = stream.read(&mut request).await.unwrap();
+            assert!(String::from_utf8_lossy(&request[..n])
+                .starts_with("GET /.well-known/mta-sts.txt HTTP/1.1"));
+
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        }
+    });
+
+    let fetcher =
+        PolicyFetcher::new(&format!("https://localhost:{}/", port), Some(CA_CERT)).unwrap();
+    assert_eq!(
+        fetcher.policy_url("example.com"),
+        format!("https://localhost:{}/.well-known/mta-sts.txt", port)
+    );
+
+    let policy = MtaStsPolicy::parse(&fetcher.fetch("example.com").await.unwrap());
+    assert_eq!(policy.mode, Some(Mode::Enforce));
+    assert_eq!(policy.mx, vec!["mx.example.com"]);
+
+    let too_large = Err(format!("Policy larger than {} bytes", MAX_POLICY_LEN));
+    assert_eq!(fetcher.fetch("example.com").await, too_large);
+    assert_eq!(fetcher.fetch("example.com").await, too_large);
+
+    let fetcher = PolicyFetcher::new(DEFAULT_POLICY_BASE_URL, None).unwrap();
+    assert_eq!(
+        fetcher.policy_url("Example.com."),
+        "https://mta-sts.example.com/.well-known/mta-sts.txt"
+    );
+}
@@ -0,0 +1,241 @@
+use crate::spf::{Mechanism, Qualifier, SpfRecord};
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use std::fmt;
+use trust_dns_client::rr::{RData, Record};
+
+/// A null MX is a single `MX 0 .` record, RFC 7505 §3.
+const NULL_MX_PREFERENCE: u16 = 0;
+const ROOT: &str = ".";
+
+#[derive(Debug, PartialEq)]
+pub enum MxFinding {
+    NoMx,
+    NullMxWithOthers,
+    NullMxPreference(u16),
+}
+
+impl fmt::Display for MxFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoMx => write!(
+                f,
+                "No MX: Senders fall back to the A record, publish a null MX if no mail is received"
+            ),
+            Self::NullMxWithOthers => write!(f, "Null MX alongside other MX records"),
+            Self::NullMxPreference(n) => write!(f, "Null MX with preference {} instead of 0", n),
+        }
+    }
+}
+
+impl MxFinding {
+    /// Findings that leave senders unsure whether the domain accepts mail.
+    pub fn is_invalid(&self) -> bool {
+        !matches!(self, Self::NoMx)
+    }
+}
+
+/// The MX records of a domain.
+#[derive(Debug, Default, PartialEq)]
+pub struct MxCheck {
+    pub exchanges: Vec<(u16, String)>,
+    pub null_mx: bool,
+    /// No MX records but an address, which senders deliver to instead, RFC 5321 §5.1.
+    pub implicit_mx: bool,
+    pub findings: Vec<MxFinding>,
+}
+
+impl fmt::Display for MxCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (preference, exchange)) in self.exchanges.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{} {}", preference, exchange)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl MxCheck {
+    pub fn from_answers(r: &[Record]) -> Self {
+        let mut exchanges: Vec<(u16, String)> = r
+            .iter()
+            .filter_map(|record| record.rdata().as_mx())
+            .map(|mx| (mx.preference(), mx.exchange().to_string()))
+            .collect();
+        exchanges.sort();
+
+        let mut findings: Vec<MxFinding> = Vec::new();
+        let null_mx = exchanges.iter().find(|(_, exchange)| exchange == ROOT);
+
+        match null_mx {
+            None if exchanges.is_empty() => findings.push(MxFinding::NoMx),
+            None => (),
+            Some(_) if exchanges.len() > 1 => findings.push(MxFinding::NullMxWithOthers),
+            Some((preference, _)) if *preference != NULL_MX_PREFERENCE => {
+                findings.push(MxFinding::NullMxPreference(*preference))
+            }
+            Some(_) => (),
+        }
+
+        Self {
+            null_mx: null_mx.is_some(),
+            implicit_mx: false,
+            exchanges,
+            findings,
+        }
+    }
+
+    /// Records whether a domain without MX records has the A or AAAA answers senders fall
+    /// back to.
+    pub fn set_addresses(&mut self, r: &[Record]) {
+        self.implicit_mx = self.exchanges.is_empty()
+            && r.iter()
+                .any(|record| matches!(record.rdata(), RData::A(_) | RData::AAAA(_)));
+    }
+
+    /// Whether the domain publishes a well formed null MX and nothing else.
+    pub fn is_null_mx(&self) -> bool {
+        self.null_mx && self.findings.is_empty()
+    }
+
+    /// Whether any MX record names a host that accepts mail, or the implicit MX does.
+    pub fn receives_mail(&self) -> bool {
+        self.implicit_mx || self.exchanges.iter().any(|(_, exchange)| exchange != ROOT)
+    }
+}
+
+/// Whether an SPF record authorizes no host at all, `v=spf1 -all`.
+pub fn is_spf_lockdown(spf_record: &SpfRecord) -> bool {
+    spf_record.errors.is_empty()
+        && spf_record.redirect.is_none()
+        && matches!(
+            spf_record.directives.as_slice(),
+            [d] if d.mechanism == Mechanism::All && d.qualifier == Qualifier::Fail
+        )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MailClass {
+    ActiveMail,
+    ParkedLocked,
+    ParkedSpoofable,
+}
+
+impl fmt::Display for MailClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ActiveMail => write!(f, "Active mail"),
+            Self::ParkedLocked => write!(f, "Parked and locked"),
+            Self::ParkedSpoofable => write!(f, "Parked but spoofable"),
+        }
+    }
+}
+
+impl MailClass {
+    /// A domain without a mail host, explicit or implicit, is parked, and locked once it has a null MX, an SPF
+    /// record authorizing no host and a DMARC `p=reject`.
+    pub fn classify(mx_check: &MxCheck, spf_lockdown: bool, policy_reject: bool) -> Self {
+        if mx_check.receives_mail() {
+            Self::ActiveMail
+        } else if mx_check.is_null_mx() && spf_lockdown && policy_reject {
+            Self::ParkedLocked
+        } else {
+            Self::ParkedSpoofable
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+fn mx_record(preference: u16, exchange: &str) -> Record {
+    use trust_dns_client::rr::{rdata::MX, Name, RData};
+
+    Record::from_rdata(
+        Name::from_utf8("example.com").unwrap(),
+        300,
+        RData::MX(MX::new(preference, Name::from_utf8(exchange).unwrap())),
+    )
+}
+
+#[test]
+fn mx_check_null_mx() {
+    let mx_check = MxCheck::from_answers(&[
+        mx_record(20, "mx2.example.com."),
+        mx_record(10, "mx1.example.com."),
+    ]);
+    assert_eq!(
+        mx_check.to_string(),
+        "10 mx1.example.com. 20 mx2.example.com."
+    );
+    assert!(mx_check.receives_mail());
+    assert_eq!(mx_check.findings, vec![]);
+
+    let mx_check = MxCheck::from_answers(&[mx_record(0, ".")]);
+    assert!(mx_check.null_mx);
+    assert!(!mx_check.receives_mail());
+    assert_eq!(mx_check.findings, vec![]);
+
+    assert_eq!(
+        MxCheck::from_answers(&[mx_record(10, "."), mx_record(20, "mx.example.com.")]).findings,
+        vec![MxFinding::NullMxWithOthers]
+    );
+    assert_eq!(
+        MxCheck::from_answers(&[mx_record(10, ".")]).findings,
+        vec![MxFinding::NullMxPreference(10)]
+    );
+    assert_eq!(MxCheck::from_answers(&[]).findings, vec![MxFinding::NoMx]);
+}
+
+#[test]
+fn mx_mail_class() {
+    let null_mx = MxCheck::from_answers(&[mx_record(0, ".")]);
+    let lockdown = is_spf_lockdown(&SpfRecord::parse("v=spf1 -all"));
+
+    assert!(lockdown);
+    assert!(!is_spf_lockdown(&SpfRecord::parse("v=spf1 ~all")));
+    assert!(!is_spf_lockdown(&SpfRecord::parse(
+        "v=spf1 ip4:192.0.2.1 -all"
+    )));
+
+    assert_eq!(
+        MailClass::classify(&null_mx, lockdown, true),
+        MailClass::ParkedLocked
+    );
+    assert_eq!(
+        MailClass::classify(&null_mx, lockdown, false),
+        MailClass::ParkedSpoofable
+    );
+    assert_eq!(
+        MailClass::classify(&MxCheck::from_answers(&[]), lockdown, true),
+        MailClass::ParkedSpoofable
+    );
+    assert_eq!(
+        MailClass::classify(
+            &MxCheck::from_answers(&[mx_record(10, "mx.example.com.")]),
+            false,
+            false
+        ),
+        MailClass::ActiveMail
+    );
+
+    // Without MX records, an address is the implicit MX
+    let a_record = Record::from_rdata(
+        trust_dns_client::rr::Name::from_utf8("example.com").unwrap(),
+        300,
+        RData::A("192.0.2.1".parse().unwrap()),
+    );
+    let mut implicit_mx = MxCheck::from_answers(&[]);
+    implicit_mx.set_addresses(std::slice::from_ref(&a_record));
+    assert!(implicit_mx.receives_mail());
+    assert_eq!(
+        MailClass::classify(&implicit_mx, lockdown, true),
+        MailClass::ActiveMail
+    );
+
+    let mut null_mx = MxCheck::from_answers(&[mx_record(0, ".")]);
+    null_mx.set_addresses(&[a_record]);
+    assert!(!null_mx.receives_mail());
+}
@@ -0,0 +1,98 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use std::fmt;
+
+const PARKED_DMARC_RECORD: &str = "v=DMARC1; p=reject; sp=reject";
+const PARKED_SPF_RECORD: &str = "v=spf1 -all";
+const NULL_MX_RECORD: &str = "0 .";
+
+/// The controls a domain sending and receiving no mail should publish, see
+/// https://www.ncsc.gov.uk/guidance/protecting-parked-domains
+#[derive(Debug, PartialEq)]
+pub struct ParkedControls {
+    /// `p=reject` applies to the domain and its subdomains.
+    pub dmarc_reject: bool,
+    /// The SPF record is `v=spf1 -all` with no other mechanisms.
+    pub spf_lockdown: bool,
+    pub null_mx: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MissingRecord {
+    Dmarc(String),
+    Spf(String),
+    NullMx(String),
+}
+
+impl fmt::Display for MissingRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Dmarc(domain) => write!(f, "_dmarc.{} TXT \"{}\"", domain, PARKED_DMARC_RECORD),
+            Self::Spf(domain) => write!(f, "{} TXT \"{}\"", domain, PARKED_SPF_RECORD),
+            Self::NullMx(domain) => write!(f, "{} MX {}", domain, NULL_MX_RECORD),
+        }
+    }
+}
+
+impl MissingRecord {
+    /// What publishing the record fixes.
+    pub fn control(&self) -> &str {
+        match self {
+            Self::Dmarc(_) => "DMARC p=reject and sp=reject",
+            Self::Spf(_) => "SPF -all with no other mechanisms",
+            Self::NullMx(_) => "Null MX replacing any other MX",
+        }
+    }
+}
+
+impl ParkedControls {
+    /// Records to publish, each replacing the record of the same type at that name.
+    pub fn missing_records(&self, domain_name: &str) -> Vec<MissingRecord> {
+        let domain_name = domain_name.trim_end_matches('.').to_lowercase();
+        let mut missing: Vec<MissingRecord> = Vec::new();
+
+        if !self.dmarc_reject {
+            missing.push(MissingRecord::Dmarc(domain_name.clone()));
+        }
+
+        if !self.spf_lockdown {
+            missing.push(MissingRecord::Spf(domain_name.clone()));
+        }
+
+        if !self.null_mx {
+            missing.push(MissingRecord::NullMx(domain_name));
+        }
+
+        missing
+    }
+}
+
+// Tests
+#[test]
+fn parked_missing_records() {
+    let locked = ParkedControls {
+        dmarc_reject: true,
+        spf_lockdown: true,
+        null_mx: true,
+    };
+    assert_eq!(locked.missing_records("example.com"), vec![]);
+
+    let open = ParkedControls {
+        dmarc_reject: false,
+        spf_lockdown: false,
+        null_mx: false,
+    };
+    let missing: Vec<String> = open
+        .missing_records("Example.com.")
+        .iter()
+        .map(|r| r.to_string())
+        .collect();
+    assert_eq!(
+        missing,
+        vec![
+            "_dmarc.example.com TXT \"v=DMARC1; p=reject; sp=reject\"",
+            "example.com TXT \"v=spf1 -all\"",
+            "example.com MX 0 .",
+        ]
+    );
+}
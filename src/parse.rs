@@ -1,7 +1,25 @@
+use crate::bimi::{BimiCheck, BimiFinding};
+use crate::cname::{ChainStatus, CnameChain, MAX_CNAME_DEPTH};
+use crate::discovery::TreeWalk;
+use crate::dkim::SelectorDiscovery;
+use crate::dkim_key::{DkimKeyCheck, SelectorFinding};
+use crate::mta_sts::{MtaStsCheck, MtaStsFinding};
+use crate::mx::{self, MailClass, MxCheck, MxFinding};
+use crate::parked::ParkedControls;
+use crate::psl::PublicSuffixList;
+use crate::score::{self, Grade, ScoreWeights};
+use crate::spf::{Qualifier, SpfCheck, SpfFinding};
+use crate::spf_expansion::{ExpansionFinding, SpfExpansion};
+use crate::spoofability::{Enforcement, Exposure, Spoofability};
+use crate::suggest::suggest;
+use crate::tag_list::{Diagnostic, TagList};
+use crate::tls_rpt::{TlsRptCheck, TlsRptFinding};
+use crate::uri::{UriList, MAILTO_SCHEME, MAX_DMARC_URIS};
 #[cfg(test)]
 use pretty_assertions::assert_eq;
-use serde::{Deserialize, Serialize, Serializer};
-use trust_dns_client::rr::{Record, RecordType};
+use serde::{Serialize, Serializer};
+use std::fmt;
+use trust_dns_client::rr::{rdata::TXT, Record, RecordType};
 
 const DMARC1: &str = "DMARC1";
 
@@ -13,16 +31,56 @@ const RUF_TAG: &str = "ruf";
 const SP_TAG: &str = "sp";
 const ADKIM_TAG: &str = "adkim";
 const ASPF_TAG: &str = "aspf";
+const FO_TAG: &str = "fo";
+const RF_TAG: &str = "rf";
+const RI_TAG: &str = "ri";
+const NP_TAG: &str = "np";
+const PSD_TAG: &str = "psd";
+const T_TAG: &str = "t";
 
 const TAG_NONE: &str = "none";
 const TAG_QURANTINE: &str = "quarantine";
 const TAG_REJECT: &str = "reject";
-const TAG_INVALID: &str = "INVALID";
+
+const ALIGNMENT_RELAXED: &str = "r";
+const ALIGNMENT_STRICT: &str = "s";
+
+const FO_ALL_FAIL: &str = "0";
+const FO_ANY_FAIL: &str = "1";
+const FO_DKIM: &str = "d";
+const FO_SPF: &str = "s";
+const FO_SEPARATOR: char = ':';
+
+const RF_AFRF: &str = "afrf";
+const RF_SEPARATOR: char = ':';
+
+const KNOWN_TAGS: &[&str] = &[
+    V_TAG, P_TAG, PCT_TAG, RUA_TAG, RUF_TAG, SP_TAG, ADKIM_TAG, ASPF_TAG, FO_TAG, RF_TAG, RI_TAG,
+    NP_TAG, PSD_TAG, T_TAG,
+];
+const KNOWN_ACTIONS: &[&str] = &[TAG_NONE, TAG_QURANTINE, TAG_REJECT];
+
+const FINDING_SEPARATOR: &str = ", ";
+
+const FLAG_YES: &str = "y";
+const FLAG_NO: &str = "n";
+const FLAG_UNKNOWN: &str = "u";
+
+const MAX_PCT: u8 = 100;
+const MIN_RI: u32 = 3600;
 
 const CNAME_RECORD: &str = "CNAME";
 const TXT_RECORD: &str = "TXT";
 const OTHER_RECORD: &str = "OTHER";
 
+const RAW_DATA_SEPARATOR: &str = " | ";
+
+/// Largest DNS response guaranteed to fit in a UDP datagram without EDNS0, RFC 1035 §4.2.1.
+const MAX_UDP_RESPONSE: usize = 512;
+const DNS_HEADER_LEN: usize = 12;
+const DNS_QUESTION_FIXED_LEN: usize = 4;
+const DNS_ANSWER_FIXED_LEN: usize = 12;
+
 const YES: &str = "Yes";
 const NO: &str = "No";
 
@@ -31,6 +89,24 @@ const ERR_MISSING_V_OR_P_FLAG: &str = "V or P flag missing";
 const ERR_FIRST_FLAG_NOT_V: &str = "First flag is not V";
 const ERR_SECOND_FLAG_NOT_P: &str = "Second flag is not P";
 const ERR_POLICY_IS_NONE: &str = "Policy is `none`";
+const ERR_PCT_NOT_PERCENTAGE: &str = "Is not a number between 0 and 100";
+const ERR_RI_NOT_NUMBER: &str = "Is not a number of seconds";
+const ERR_RI_TOO_SHORT: &str = "Receivers are only required to report hourly";
+const ERR_UNAUTHORIZED_REPORT_DOMAINS: &str =
+    "Missing _report._dmarc authorization, reports will be dropped for";
+const ERR_NP_IS_NONE: &str = "np=none: Permits spoofing of non-existent subdomains";
+const ERR_TESTING_MODE: &str = "t=y: Receivers apply a policy one level less strict";
+const ERR_DEPRECATED_TAGS: &str = "Removed in DMARCbis";
+const ERR_IGNORED_RECORDS: &str = "TXT records without v=DMARC1 ignored";
+const ERR_CNAME_LOOP: &str = "CNAME loop at";
+const ERR_CNAME_DEPTH: &str = "CNAME chain longer than";
+const ERR_CNAME_NO_RECORD: &str = "No DMARC record at";
+const ERR_STRING_SPLIT_IN_TAG: &str = "String boundary inside tag";
+const ERR_UDP_TRUNCATED: &str = "bytes: Response exceeds 512 byte UDP limit, needs TCP or EDNS0";
+const ERR_DUPLICATE_TAG: &str = "Duplicate tag";
+const ERR_PARKED_MISSING: &str = "Parked domain missing";
+const ERR_MULTIPLE_RECORDS: &str = "records: DMARC not applied";
+const ERR_FO_WITHOUT_RUF: &str = "fo has no effect without ruf";
 
 const ERR_P_FLAG_MISSING_AND_SP_NOT_SET: &str = "Missing p flag and sp not set";
 const ERR_P_FLAG_MISSING: &str = "P flag missing";
@@ -38,14 +114,6 @@ const ERR_IGNORE_SUBDOMAIN_DMARC_FAILS: &str = "sp=none: Ignores subdomain DMARC
 const ERR_PERMITS_SUBDOMAIN_SPOOFING: &str =
     "p=reject, sp=none: Ignores subdomain DMARC fails and permits subdomain spoofing";
 
-#[derive(Debug, Deserialize)]
-pub struct DomainName(pub String);
-
-#[derive(Serialize)]
-pub struct ParseResult {
-    pub domain_name: String,
-}
-
 #[derive(Debug)]
 pub enum StringRecords {
     Single(DmarcRecordType),
@@ -60,39 +128,107 @@ pub enum DmarcRecordType {
 }
 
 impl DmarcRecordType {
-    fn new(r: &Record) -> Self {
+    pub fn new(r: &Record) -> Self {
         match r.rdata().to_record_type() {
-            RecordType::CNAME => Self::Cname(
-                r.rdata()
-                    .as_cname()
-                    .and_then(|cname| Some(cname.to_string())),
-            ),
-            RecordType::TXT => Self::Txt(
-                r.rdata()
-                    .as_txt()
-                    .and_then(|txt_data| Some(txt_data.to_string())),
-            ),
+            RecordType::CNAME => Self::Cname(r.rdata().as_cname().map(|cname| cname.to_string())),
+            RecordType::TXT => Self::Txt(r.rdata().as_txt().map(join_txt_strings)),
             _ => Self::Other,
         }
     }
+
+    /// Returns the TXT data when it is a DMARC record, i.e. it starts with `v=DMARC1`.
+    pub fn dmarc_txt(self) -> Option<String> {
+        match self.is_dmarc() {
+            true => match self {
+                Self::Txt(txt) => txt,
+                _ => None,
+            },
+            false => None,
+        }
+    }
+
+    fn is_dmarc(&self) -> bool {
+        match self {
+            Self::Txt(Some(txt)) => TagList::parse(txt)
+                .tags
+                .first()
+                .is_some_and(|t| t.name == V_TAG && t.value == DMARC1),
+            _ => false,
+        }
+    }
+
+    fn is_txt(&self) -> bool {
+        matches!(self, Self::Txt(_))
+    }
+}
+
+/// Concatenates the character-strings of a TXT record without adding any separator, as
+/// RFC 7489 §6.6.3 requires for records longer than 255 bytes.
+pub fn join_txt_strings(txt: &TXT) -> String {
+    txt.iter()
+        .map(|s| String::from_utf8_lossy(s))
+        .collect::<Vec<_>>()
+        .concat()
+}
+
+/// Length of `name` in DNS wire format.
+fn wire_name_len(name: &str) -> usize {
+    name.trim_end_matches('.')
+        .split('.')
+        .filter(|label| !label.is_empty())
+        .map(|label| label.len() + 1)
+        .sum::<usize>()
+        + 1
+}
+
+/// Length of the RDATA of a TXT record, every string carries a length byte.
+fn txt_rdata_len(txt: &TXT) -> usize {
+    txt.iter().map(|s| s.len() + 1).sum()
 }
 
 impl StringRecords {
+    /// Selects the DMARC records from the answers, see RFC 7489 §6.6.3.
+    ///
+    /// TXT records that do not start with `v=DMARC1` are discarded. Other record types are
+    /// only kept when no DMARC record was returned, so a bare CNAME is still reported.
     pub fn new(r: &[Record]) -> Option<Self> {
-        match r.len() {
-            0 => None,
-            1 => Some(Self::Single(DmarcRecordType::new(&r[0]))),
-            n @ _ => {
-                let mut dmarc_records: Vec<DmarcRecordType> = Vec::with_capacity(n);
-
-                for i in r.iter() {
-                    dmarc_records.push(DmarcRecordType::new(i))
-                }
+        let (mut dmarc_records, others): (Vec<DmarcRecordType>, Vec<DmarcRecordType>) = r
+            .iter()
+            .map(DmarcRecordType::new)
+            .partition(|d| d.is_dmarc());
+
+        // A CNAME chain that never reaches a DMARC record is reported by its first link
+        if dmarc_records.is_empty() {
+            let mut cname_seen = false;
+
+            dmarc_records = others
+                .into_iter()
+                .filter(|d| match d {
+                    DmarcRecordType::Txt(_) => false,
+                    DmarcRecordType::Cname(_) => !std::mem::replace(&mut cname_seen, true),
+                    DmarcRecordType::Other => true,
+                })
+                .collect();
+        }
 
-                Some(Self::Multiple(dmarc_records))
-            }
+        match dmarc_records.len() {
+            0 => None,
+            1 => dmarc_records.pop().map(Self::Single),
+            _ => Some(Self::Multiple(dmarc_records)),
         }
     }
+
+    /// TXT records returned alongside, or instead of, the DMARC records.
+    pub fn ignored(r: &[Record]) -> Vec<String> {
+        r.iter()
+            .map(DmarcRecordType::new)
+            .filter(|d| d.is_txt() && !d.is_dmarc())
+            .map(|d| match d {
+                DmarcRecordType::Txt(txt) => format!("\"{}\"", txt.unwrap_or_default()),
+                _ => String::new(),
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -124,6 +260,57 @@ impl DmarcVersion {
     }
 }
 
+/// Removes every repeat of a tag, returning a finding naming the tag and both values.
+///
+/// Repeated tags make the whole tag-list invalid (RFC 6376 §3.2, which RFC 7489 §6.4
+/// follows), so a receiver applies none of the record.
+fn remove_duplicate_tags(dmarc_entries: &mut Vec<DmarcEntry>) -> Vec<String> {
+    let mut findings: Vec<String> = Vec::new();
+    let mut i = 0;
+
+    while i < dmarc_entries.len() {
+        match dmarc_entries[..i]
+            .iter()
+            .find(|e| e.tag == dmarc_entries[i].tag)
+        {
+            Some(first) => {
+                findings.push(format!(
+                    "{} `{}`: `{}` and `{}`",
+                    ERR_DUPLICATE_TAG, first.tag, first.val, dmarc_entries[i].val
+                ));
+                dmarc_entries.remove(i);
+            }
+            None => i += 1,
+        }
+    }
+
+    findings
+}
+
+fn suggest_value(tag: &str, val: &str, vocabulary: &[&str]) -> Option<String> {
+    suggest(val, vocabulary).map(|v| format!("`{}={}`: did you mean `{}={}`?", tag, val, tag, v))
+}
+
+/// Suggests the right version for a TXT record discarded because of a misspelled `v=DMARC1`.
+fn suggest_version(txt: &str) -> Option<String> {
+    let tag_list = TagList::parse(txt);
+    let v = tag_list.tags.first()?;
+
+    match v.name == V_TAG || suggest(v.name, &[V_TAG]).is_some() {
+        true if v.value == DMARC1 => Some(format!(
+            "`{}={}`: did you mean `{}={}`?",
+            v.name, v.value, V_TAG, DMARC1
+        )),
+        true => suggest(v.value, &[DMARC1]).map(|_| {
+            format!(
+                "`{}={}`: did you mean `{}={}`?",
+                v.name, v.value, V_TAG, DMARC1
+            )
+        }),
+        false => None,
+    }
+}
+
 fn match_tag<'a>(tag: &str, dmarc_entries: &'a mut Vec<DmarcEntry>) -> Option<DmarcEntry<'a>> {
     for (i, e) in dmarc_entries.iter_mut().enumerate() {
         if e.tag == tag {
@@ -131,10 +318,10 @@ fn match_tag<'a>(tag: &str, dmarc_entries: &'a mut Vec<DmarcEntry>) -> Option<Dm
         }
     }
 
-    return None;
+    None
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 enum TagAction {
     None,
     Qurantine,
@@ -159,6 +346,15 @@ impl Serialize for TagAction {
 }
 
 impl TagAction {
+    fn enforcement(&self) -> Option<Enforcement> {
+        match self {
+            Self::None => Some(Enforcement::None),
+            Self::Qurantine => Some(Enforcement::Quarantine),
+            Self::Reject => Some(Enforcement::Reject),
+            Self::Invalid(_) => None,
+        }
+    }
+
     fn to_enum(p_tag_val: &str) -> Self {
         match p_tag_val.to_lowercase().as_str() {
             TAG_NONE => Self::None,
@@ -170,561 +366,2577 @@ impl TagAction {
 }
 
 #[derive(Debug, PartialEq)]
-struct DmarcEntry<'a> {
-    tag: &'a str,
-    val: &'a str,
+enum Percentage {
+    Value(u8),
+    Invalid(String),
 }
 
-impl<'a> DmarcEntry<'a> {
-    pub fn new(tag: &'a str, val: &'a str) -> Self {
-        Self { tag, val }
+impl Serialize for Percentage {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            Self::Value(n) => serializer.serialize_u8(n),
+            Self::Invalid(ref s) => {
+                serializer.serialize_newtype_variant("Invalid", 1, "Invalid", s)
+            }
+        }
     }
 }
-#[derive(Debug, Default, PartialEq, Serialize)]
-pub struct Dmarc {
-    domain_name: String,
-    returned_record: String,
-    record_type: String,
-    v: Option<DmarcVersion>,
-    p: Option<TagAction>,
-    pct: Option<String>, // TODO: Should change this to a u8 later
-    rua: Option<String>,
-    ruf: Option<String>,
-    sp: Option<TagAction>,
-    adkim: Option<String>,
-    aspf: Option<String>,
-    others: Option<String>,
-    invalid_flags: Option<String>,
-    config_v_p_order: Option<String>,
-    config_v: Option<String>,
-    config_p: Option<String>,
-    config_pct: Option<String>,
-    config_sp: Option<String>,
-    raw_data: String,
+
+impl Percentage {
+    fn to_pct(pct_tag_val: &str) -> Self {
+        match pct_tag_val.parse::<u8>() {
+            Ok(n) if n <= MAX_PCT => Self::Value(n),
+            _ => Self::Invalid(pct_tag_val.to_string()),
+        }
+    }
 }
 
-impl Dmarc {
-    pub fn new(domain_name: &str, dmarc_record: Option<DmarcRecordType>) -> Self {
-        let mut dmarc = Self::default();
-        dmarc.domain_name = domain_name.to_string();
+#[derive(Debug, PartialEq)]
+enum AlignmentMode {
+    Relaxed,
+    Strict,
+    Invalid(String),
+}
 
-        let dmarc_parsed = match dmarc_record {
-            Some(ref r) => match r {
-                DmarcRecordType::Cname(opt_url) => {
-                    dmarc.record_type = CNAME_RECORD.to_string();
-                    dmarc.returned_record = YES.to_string();
-                    if let Some(url) = opt_url {
-                        dmarc.raw_data = url.to_string();
-                    }
-                    return dmarc;
-                }
-                DmarcRecordType::Txt(ref opt_txt) => match opt_txt {
-                    Some(ref txt) => {
-                        dmarc.record_type = TXT_RECORD.to_string();
-                        dmarc.returned_record = YES.to_string();
-                        DmarcParsed::new(txt)
-                    }
-                    None => {
-                        dmarc.record_type = TXT_RECORD.to_string();
-                        dmarc.returned_record = YES.to_string();
-                        return dmarc;
-                    }
-                },
-                DmarcRecordType::Other => {
-                    dmarc.record_type = OTHER_RECORD.to_string();
-                    dmarc.returned_record = YES.to_string();
-                    return dmarc;
-                }
-            },
-            None => {
-                dmarc.returned_record = NO.to_string();
-                return dmarc;
+impl Serialize for AlignmentMode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            Self::Relaxed => serializer.serialize_unit_variant("Relaxed", 0, ALIGNMENT_RELAXED),
+            Self::Strict => serializer.serialize_unit_variant("Strict", 1, ALIGNMENT_STRICT),
+            Self::Invalid(ref s) => {
+                serializer.serialize_newtype_variant("Invalid", 2, "Invalid", s)
             }
-        };
+        }
+    }
+}
 
-        let mut dmarc_entries = match dmarc_parsed.dmarc_entries {
-            Some(de) => de,
-            None => {
-                dmarc.invalid_flags = dmarc_parsed.invalid_entries;
-                dmarc.raw_data = dmarc_parsed.raw_txt;
-                return dmarc;
-            }
-        };
+impl AlignmentMode {
+    fn to_enum(alignment_tag_val: &str) -> Self {
+        match alignment_tag_val.to_ascii_lowercase().as_str() {
+            ALIGNMENT_RELAXED => Self::Relaxed,
+            ALIGNMENT_STRICT => Self::Strict,
+            _ => Self::Invalid(alignment_tag_val.to_string()),
+        }
+    }
+}
 
-        let config_v_p_order = Self::check_v_and_p_order(&dmarc_entries).to_string();
+#[derive(Debug, PartialEq)]
+enum FailureOption {
+    AllFail,
+    AnyFail,
+    Dkim,
+    Spf,
+    Invalid(String),
+}
 
-        dmarc.v = match_tag(V_TAG, &mut dmarc_entries)
-            .and_then(|v_entry| Some(DmarcVersion::to_ver(v_entry.val)));
+impl fmt::Display for FailureOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::AllFail => write!(f, "{}", FO_ALL_FAIL),
+            Self::AnyFail => write!(f, "{}", FO_ANY_FAIL),
+            Self::Dkim => write!(f, "{}", FO_DKIM),
+            Self::Spf => write!(f, "{}", FO_SPF),
+            Self::Invalid(s) => write!(f, "{}", s),
+        }
+    }
+}
 
-        dmarc.p = match_tag(P_TAG, &mut dmarc_entries)
-            .and_then(|p_entry| Some(TagAction::to_enum(p_entry.val)));
+impl FailureOption {
+    fn to_enum(fo_val: &str) -> Self {
+        match fo_val.to_ascii_lowercase().as_str() {
+            FO_ALL_FAIL => Self::AllFail,
+            FO_ANY_FAIL => Self::AnyFail,
+            FO_DKIM => Self::Dkim,
+            FO_SPF => Self::Spf,
+            _ => Self::Invalid(fo_val.to_string()),
+        }
+    }
+}
 
-        dmarc.pct = match_tag(PCT_TAG, &mut dmarc_entries)
-            .and_then(|pct_entry| Some(pct_entry.val.to_string()));
+#[derive(Debug, PartialEq)]
+struct FailureOptions(Vec<FailureOption>);
 
-        dmarc.rua = match_tag(RUA_TAG, &mut dmarc_entries)
-            .and_then(|rua_entry| Some(rua_entry.val.to_string()));
+impl Serialize for FailureOptions {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(&join(&self.0, FO_SEPARATOR))
+    }
+}
 
-        dmarc.ruf = match_tag(RUF_TAG, &mut dmarc_entries)
-            .and_then(|ruf_entry| Some(ruf_entry.val.to_string()));
+impl FailureOptions {
+    fn to_options(fo_tag_val: &str) -> Self {
+        let mut options: Vec<FailureOption> = Vec::new();
 
-        dmarc.sp = match_tag(SP_TAG, &mut dmarc_entries)
-            .and_then(|sp_entry| Some(TagAction::to_enum(sp_entry.val)));
+        for o in fo_tag_val.split(FO_SEPARATOR).map(|o| o.trim()) {
+            let o = FailureOption::to_enum(o);
+            if !options.contains(&o) {
+                options.push(o)
+            }
+        }
 
-        dmarc.adkim = match_tag(ADKIM_TAG, &mut dmarc_entries)
-            .and_then(|adkim_entry| Some(adkim_entry.val.to_string()));
+        Self(options)
+    }
+}
 
-        dmarc.aspf = match_tag(ASPF_TAG, &mut dmarc_entries)
-            .and_then(|aspf_entry| Some(aspf_entry.val.to_string()));
+#[derive(Debug, PartialEq)]
+enum ReportFormat {
+    Afrf,
+    Invalid(String),
+}
 
-        dmarc.others = match dmarc_entries.is_empty() {
-            true => None,
-            false => {
-                let mut others_tmp = String::new();
-                for e in dmarc_entries {
-                    others_tmp.push_str(&format!("{}={} ", e.tag, e.val));
-                }
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Afrf => write!(f, "{}", RF_AFRF),
+            Self::Invalid(s) => write!(f, "{}", s),
+        }
+    }
+}
 
-                Some(others_tmp)
-            }
-        };
+impl ReportFormat {
+    fn to_enum(rf_val: &str) -> Self {
+        match rf_val.to_lowercase().as_str() {
+            RF_AFRF => Self::Afrf,
+            _ => Self::Invalid(rf_val.to_string()),
+        }
+    }
+}
 
-        dmarc.invalid_flags = dmarc_parsed.invalid_entries;
-        dmarc.config_v_p_order = Some(config_v_p_order);
-        dmarc.config_v = Some(dmarc.check_v().to_string());
-        dmarc.config_p = Some(dmarc.check_p().to_string());
-        dmarc.config_pct = Some(dmarc.check_pct().to_string());
-        dmarc.config_sp = Some(dmarc.check_sp().to_string());
-        dmarc.raw_data = dmarc_parsed.raw_txt;
+#[derive(Debug, PartialEq)]
+struct ReportFormats(Vec<ReportFormat>);
 
-        dmarc
+impl Serialize for ReportFormats {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(&join(&self.0, RF_SEPARATOR))
     }
+}
 
-    fn check_v(&self) -> DmarcFieldResult {
-        match &self.v {
-            Some(ver) => match ver {
-                DmarcVersion::Dmarc1 => DmarcFieldResult::ValidConfig,
-                DmarcVersion::Invalid(s) => DmarcFieldResult::InvalidConfig(s.clone()),
-            },
-            None => DmarcFieldResult::InvalidConfig(ERR_FLAG_NOT_PRESENT.to_string()),
-        }
+impl ReportFormats {
+    fn to_formats(rf_tag_val: &str) -> Self {
+        Self(
+            rf_tag_val
+                .split(RF_SEPARATOR)
+                .map(|rf| ReportFormat::to_enum(rf.trim()))
+                .collect(),
+        )
     }
+}
 
-    fn check_p(&self) -> DmarcFieldResult {
-        match &self.p {
-            Some(v) => match v {
-                TagAction::Invalid(s) => DmarcFieldResult::InvalidConfig(s.clone()),
-                TagAction::None => DmarcFieldResult::VeryBadConfig(TAG_NONE.to_string()),
-                _ => DmarcFieldResult::ValidConfig,
-            },
-            None => DmarcFieldResult::InvalidConfig(ERR_FLAG_NOT_PRESENT.to_string()),
-        }
-    }
+#[derive(Debug, PartialEq)]
+enum ReportInterval {
+    Seconds(u32),
+    Invalid(String),
+}
 
-    fn check_pct(&self) -> DmarcFieldResult {
-        match &self.pct {
-            Some(pct) => match &pct.parse::<u8>() {
-                Ok(n) => match &self.p {
-                    Some(p) => match p {
-                        TagAction::None => {
-                            DmarcFieldResult::VeryBadConfig(ERR_POLICY_IS_NONE.to_string())
-                        }
-                        TagAction::Qurantine => {
-                            if *n < 25 {
-                                DmarcFieldResult::VeryBadConfig(pct.clone())
-                            } else if *n < 100 {
-                                DmarcFieldResult::BadConfig(pct.clone())
-                            } else if *n > 100 {
-                                DmarcFieldResult::InvalidConfig(pct.clone())
-                            } else {
-                                DmarcFieldResult::ValidConfig
-                            }
-                        }
-                        TagAction::Reject => match *n > 100 {
-                            true => DmarcFieldResult::InvalidConfig(pct.clone()),
-                            false => DmarcFieldResult::ValidConfig,
-                        },
-                        TagAction::Invalid(i) => DmarcFieldResult::InvalidConfig(i.clone()),
-                    },
-                    None => DmarcFieldResult::InvalidConfig(ERR_P_FLAG_MISSING.to_string()),
-                },
-                Err(_) => DmarcFieldResult::InvalidConfig(format!(
-                    "{} <- {}",
-                    pct,
-                    "Is not a number".to_string()
-                )),
-            },
-            None => DmarcFieldResult::ValidConfig,
+impl Serialize for ReportInterval {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            Self::Seconds(n) => serializer.serialize_u32(n),
+            Self::Invalid(ref s) => {
+                serializer.serialize_newtype_variant("Invalid", 1, "Invalid", s)
+            }
         }
     }
+}
 
-    fn check_v_and_p_order(dmarc_entries: &Vec<DmarcEntry>) -> DmarcFieldResult {
-        if dmarc_entries.len() < 2 {
-            return DmarcFieldResult::InvalidConfig(ERR_MISSING_V_OR_P_FLAG.to_string());
+impl ReportInterval {
+    fn to_interval(ri_tag_val: &str) -> Self {
+        match ri_tag_val.parse::<u32>() {
+            Ok(n) => Self::Seconds(n),
+            Err(_) => Self::Invalid(ri_tag_val.to_string()),
         }
+    }
+}
 
-        let (v, p) = (&dmarc_entries[0], &dmarc_entries[1]);
+#[derive(Debug, PartialEq)]
+enum PublicSuffixDomain {
+    Yes,
+    No,
+    Unknown,
+    Invalid(String),
+}
 
-        if v.tag != V_TAG {
-            return DmarcFieldResult::InvalidConfig(ERR_FIRST_FLAG_NOT_V.to_string());
+impl Serialize for PublicSuffixDomain {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            Self::Yes => serializer.serialize_unit_variant("Yes", 0, FLAG_YES),
+            Self::No => serializer.serialize_unit_variant("No", 1, FLAG_NO),
+            Self::Unknown => serializer.serialize_unit_variant("Unknown", 2, FLAG_UNKNOWN),
+            Self::Invalid(ref s) => {
+                serializer.serialize_newtype_variant("Invalid", 3, "Invalid", s)
+            }
         }
+    }
+}
 
-        if p.tag != P_TAG {
-            return DmarcFieldResult::InvalidConfig(ERR_SECOND_FLAG_NOT_P.to_string());
+impl PublicSuffixDomain {
+    fn to_enum(psd_tag_val: &str) -> Self {
+        match psd_tag_val {
+            FLAG_YES => Self::Yes,
+            FLAG_NO => Self::No,
+            FLAG_UNKNOWN => Self::Unknown,
+            _ => Self::Invalid(psd_tag_val.to_string()),
         }
-
-        DmarcFieldResult::ValidConfig
     }
+}
 
-    fn check_sp(&self) -> DmarcFieldResult {
-        let sp = match &self.sp {
-            Some(sp) => sp,
-            None => match &self.p {
-                Some(p) => p,
-                None => {
-                    return DmarcFieldResult::InvalidConfig(
-                        ERR_P_FLAG_MISSING_AND_SP_NOT_SET.to_string(),
-                    )
+#[derive(Debug, PartialEq)]
+enum TestingMode {
+    Yes,
+    No,
+    Invalid(String),
+}
+
+impl Serialize for TestingMode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            Self::Yes => serializer.serialize_unit_variant("Yes", 0, FLAG_YES),
+            Self::No => serializer.serialize_unit_variant("No", 1, FLAG_NO),
+            Self::Invalid(ref s) => {
+                serializer.serialize_newtype_variant("Invalid", 2, "Invalid", s)
+            }
+        }
+    }
+}
+
+impl TestingMode {
+    fn to_enum(t_tag_val: &str) -> Self {
+        match t_tag_val {
+            FLAG_YES => Self::Yes,
+            FLAG_NO => Self::No,
+            _ => Self::Invalid(t_tag_val.to_string()),
+        }
+    }
+}
+
+fn join<T: fmt::Display, S: ToString>(values: &[T], separator: S) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>()
+        .join(&separator.to_string())
+}
+
+/// Grades a check by its findings: valid when there are none, invalid when any finding is
+/// invalid and bad otherwise, listing every finding.
+fn field_result<F: fmt::Display>(
+    findings: &[F],
+    is_invalid: impl Fn(&F) -> bool,
+) -> DmarcFieldResult {
+    let findings_str = join(findings, FINDING_SEPARATOR);
+
+    if findings.is_empty() {
+        DmarcFieldResult::ValidConfig
+    } else if findings.iter().any(is_invalid) {
+        DmarcFieldResult::InvalidConfig(findings_str)
+    } else {
+        DmarcFieldResult::BadConfig(findings_str)
+    }
+}
+
+/// Result of the RFC 7489 §7.1 check that a third party accepts reports for a domain.
+#[derive(Debug, PartialEq)]
+pub struct ReportAuthorization {
+    pub target_domain: String,
+    authorized: bool,
+}
+
+impl ReportAuthorization {
+    pub fn new(target_domain: &str, r: &[Record]) -> Self {
+        // Any TXT record beginning with `v=DMARC1` authorizes the destination
+        let authorized = r
+            .iter()
+            .any(|record| DmarcRecordType::new(record).dmarc_txt().is_some());
+
+        Self {
+            target_domain: target_domain.to_string(),
+            authorized,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct DmarcEntry<'a> {
+    tag: &'a str,
+    val: &'a str,
+}
+
+impl<'a> DmarcEntry<'a> {
+    pub fn new(tag: &'a str, val: &'a str) -> Self {
+        Self { tag, val }
+    }
+}
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Dmarc {
+    domain_name: String,
+    score: Option<u32>,
+    grade: Option<Grade>,
+    spoofability: Option<String>,
+    spoofability_reasons: Option<String>,
+    mail_class: Option<String>,
+    parked_missing_records: Option<String>,
+    config_parked: Option<DmarcFieldResult>,
+    returned_record: String,
+    record_type: String,
+    record_count: usize,
+    config_record_count: Option<DmarcFieldResult>,
+    ignored_records: Option<String>,
+    config_ignored_records: Option<DmarcFieldResult>,
+    cname_chain: Option<String>,
+    config_cname_chain: Option<DmarcFieldResult>,
+    txt_string_lengths: Option<String>,
+    config_txt_strings: Option<DmarcFieldResult>,
+    organizational_domain: Option<String>,
+    policy_domain: Option<String>,
+    applied_policy: Option<TagAction>,
+    applied_via: Option<String>,
+    tree_walk_queries: Option<String>,
+    tree_walk_policy_domain: Option<String>,
+    tree_walk_applied_policy: Option<TagAction>,
+    tree_walk_applied_via: Option<String>,
+    v: Option<DmarcVersion>,
+    p: Option<TagAction>,
+    pct: Option<Percentage>,
+    #[serde(skip)]
+    rua: Option<UriList>,
+    rua_addresses: Option<String>,
+    rua_domains: Option<String>,
+    rua_size_limits: Option<String>,
+    #[serde(skip)]
+    ruf: Option<UriList>,
+    ruf_addresses: Option<String>,
+    ruf_domains: Option<String>,
+    ruf_size_limits: Option<String>,
+    sp: Option<TagAction>,
+    adkim: Option<AlignmentMode>,
+    aspf: Option<AlignmentMode>,
+    fo: Option<FailureOptions>,
+    rf: Option<ReportFormats>,
+    ri: Option<ReportInterval>,
+    np: Option<TagAction>,
+    psd: Option<PublicSuffixDomain>,
+    t: Option<TestingMode>,
+    others: Option<String>,
+    suggestions: Option<String>,
+    syntax_errors: Option<String>,
+    config_syntax: Option<DmarcFieldResult>,
+    #[serde(skip)]
+    policy_garbled: bool,
+    config_duplicate_tags: Option<DmarcFieldResult>,
+    config_v_p_order: Option<DmarcFieldResult>,
+    config_v: Option<DmarcFieldResult>,
+    config_p: Option<DmarcFieldResult>,
+    config_pct: Option<DmarcFieldResult>,
+    config_sp: Option<DmarcFieldResult>,
+    config_rua: Option<DmarcFieldResult>,
+    config_ruf: Option<DmarcFieldResult>,
+    external_report_domains: Option<String>,
+    config_report_auth: Option<DmarcFieldResult>,
+    config_adkim: Option<DmarcFieldResult>,
+    config_aspf: Option<DmarcFieldResult>,
+    config_fo: Option<DmarcFieldResult>,
+    config_rf: Option<DmarcFieldResult>,
+    config_ri: Option<DmarcFieldResult>,
+    config_np: Option<DmarcFieldResult>,
+    config_psd: Option<DmarcFieldResult>,
+    config_t: Option<DmarcFieldResult>,
+    config_deprecated: Option<DmarcFieldResult>,
+    mx_records: Option<String>,
+    config_mx: Option<DmarcFieldResult>,
+    spf_record: Option<String>,
+    spf_directives: Option<String>,
+    spf_all: Option<String>,
+    config_spf: Option<DmarcFieldResult>,
+    spf_lookups: Option<usize>,
+    spf_void_lookups: Option<usize>,
+    spf_include_tree: Option<String>,
+    config_spf_lookups: Option<DmarcFieldResult>,
+    dkim_selectors_probed: Option<usize>,
+    dkim_selectors: Option<String>,
+    dkim_keys: Option<String>,
+    config_dkim: Option<DmarcFieldResult>,
+    mta_sts_id: Option<String>,
+    mta_sts_mode: Option<String>,
+    mta_sts_mx: Option<String>,
+    mta_sts_max_age: Option<u32>,
+    config_mta_sts: Option<DmarcFieldResult>,
+    tls_rpt_rua: Option<String>,
+    config_tls_rpt: Option<DmarcFieldResult>,
+    bimi_logo: Option<String>,
+    bimi_authority: Option<String>,
+    config_bimi: Option<DmarcFieldResult>,
+    raw_data: String,
+}
+
+impl Dmarc {
+    /// Builds the result for the records returned for `_dmarc.<policy_domain>`.
+    pub fn from_answers(domain_name: &str, policy_domain: &str, r: &[Record]) -> Self {
+        let mut dmarc = match StringRecords::new(r) {
+            Some(StringRecords::Single(s)) => Self::new(domain_name, Some(s)),
+            Some(StringRecords::Multiple(vs)) => Self::multiple(domain_name, vs),
+            None => Self::new(domain_name, None),
+        };
+
+        let ignored = StringRecords::ignored(r);
+
+        // A misspelled version tag hides the record, which is worth a hint when nothing applies
+        if !dmarc.has_record() || dmarc.record_type != TXT_RECORD {
+            let version_suggestions: Vec<String> = r
+                .iter()
+                .filter_map(|rec| match DmarcRecordType::new(rec) {
+                    DmarcRecordType::Txt(Some(txt)) => suggest_version(&txt),
+                    _ => None,
+                })
+                .collect();
+
+            if !version_suggestions.is_empty() {
+                dmarc.suggestions = Some(version_suggestions.join(FINDING_SEPARATOR));
+            }
+        }
+
+        if !ignored.is_empty() {
+            dmarc.config_ignored_records = Some(DmarcFieldResult::BadConfig(format!(
+                "{} {}",
+                ignored.len(),
+                ERR_IGNORED_RECORDS
+            )));
+            dmarc.ignored_records = Some(ignored.join(RAW_DATA_SEPARATOR));
+        }
+
+        if dmarc.record_count == 1 && dmarc.record_type == TXT_RECORD {
+            dmarc.check_txt_strings(r);
+        }
+
+        if dmarc.has_record() {
+            dmarc.set_policy_domain(policy_domain);
+        }
+
+        dmarc
+    }
+
+    /// Suggestions for tag values that are near misses of a valid value.
+    fn value_suggestions(&self) -> Vec<String> {
+        let mut suggestions: Vec<String> = Vec::new();
+
+        if let Some(DmarcVersion::Invalid(v)) = &self.v {
+            suggestions.extend(suggest_value(V_TAG, v, &[DMARC1]));
+        }
+
+        for (tag, action) in [(P_TAG, &self.p), (SP_TAG, &self.sp), (NP_TAG, &self.np)].iter() {
+            if let Some(TagAction::Invalid(val)) = action {
+                suggestions.extend(suggest_value(tag, val, KNOWN_ACTIONS));
+            }
+        }
+
+        if let Some(ReportFormats(formats)) = &self.rf {
+            for format in formats {
+                if let ReportFormat::Invalid(val) = format {
+                    suggestions.extend(suggest_value(RF_TAG, val, &[RF_AFRF]));
+                }
+            }
+        }
+
+        suggestions
+    }
+
+    /// Checks how the DMARC record is split into character-strings and whether the answer
+    /// fits in a plain UDP response.
+    fn check_txt_strings(&mut self, r: &[Record]) {
+        let record = match r.iter().find(|rec| DmarcRecordType::new(rec).is_dmarc()) {
+            Some(record) => record,
+            None => return,
+        };
+        let txt = match record.rdata().as_txt() {
+            Some(txt) => txt,
+            None => return,
+        };
+
+        let joined = join_txt_strings(txt);
+        let tag_list = TagList::parse(&joined);
+        let mut findings: Vec<String> = Vec::new();
+        let mut boundary = 0;
+
+        for s in txt.iter().take(txt.txt_data().len() - 1) {
+            boundary += s.len();
+
+            if let Some(tag) = tag_list
+                .tags
+                .iter()
+                .find(|t| t.span().start < boundary && boundary < t.span().end)
+            {
+                findings.push(format!(
+                    "{} `{}` at {}",
+                    ERR_STRING_SPLIT_IN_TAG, tag.name, boundary
+                ));
+            }
+        }
+
+        let name = record.name().to_string();
+        let response_len = DNS_HEADER_LEN
+            + wire_name_len(&name)
+            + DNS_QUESTION_FIXED_LEN
+            + r.iter()
+                .filter(|rec| rec.name() == record.name())
+                .filter_map(|rec| rec.rdata().as_txt())
+                .map(|txt| DNS_ANSWER_FIXED_LEN + txt_rdata_len(txt))
+                .sum::<usize>();
+
+        if response_len > MAX_UDP_RESPONSE {
+            findings.push(format!("{} {}", response_len, ERR_UDP_TRUNCATED));
+        }
+
+        let lengths: Vec<usize> = txt.iter().map(|s| s.len()).collect();
+
+        self.txt_string_lengths = Some(join(&lengths, " "));
+        self.config_txt_strings = Some(match findings.is_empty() {
+            true => DmarcFieldResult::ValidConfig,
+            false => DmarcFieldResult::BadConfig(findings.join(FINDING_SEPARATOR)),
+        });
+    }
+
+    /// Records the CNAME records followed from `_dmarc` to the published record.
+    pub fn set_cname_chain(&mut self, cname_chain: &CnameChain) {
+        if cname_chain.depth() == 0 {
+            return;
+        }
+
+        self.cname_chain = Some(cname_chain.to_string());
+        self.config_cname_chain = Some(match &cname_chain.status {
+            ChainStatus::Complete if self.record_type == CNAME_RECORD => {
+                DmarcFieldResult::InvalidConfig(format!(
+                    "{} {}",
+                    ERR_CNAME_NO_RECORD,
+                    cname_chain.names.last().cloned().unwrap_or_default()
+                ))
+            }
+            ChainStatus::Complete => DmarcFieldResult::ValidConfig,
+            ChainStatus::Loop(name) => {
+                DmarcFieldResult::InvalidConfig(format!("{} {}", ERR_CNAME_LOOP, name))
+            }
+            ChainStatus::DepthExceeded => {
+                DmarcFieldResult::InvalidConfig(format!("{} {}", ERR_CNAME_DEPTH, MAX_CNAME_DEPTH))
+            }
+        });
+    }
+
+    /// Carries over the CNAME chain that dead-ended at the domain when the organizational
+    /// domain's record is used instead, unless that record was itself reached through a chain.
+    pub fn keep_cname_chain(&mut self, domain_dmarc: &Dmarc) {
+        if self.cname_chain.is_none() {
+            self.cname_chain = domain_dmarc.cname_chain.clone();
+            self.config_cname_chain = domain_dmarc.config_cname_chain.clone();
+        }
+    }
+
+    /// Records the SPF record published at the domain itself and the problems found with it.
+    pub fn set_spf(&mut self, spf_check: &SpfCheck) {
+        self.spf_record = spf_check.raw_txt.clone();
+
+        if let Some(record) = &spf_check.record {
+            self.spf_directives = Some(join(&record.directives, " "));
+            self.spf_all = record.all().map(|q| format!("{}all", q));
+        }
+
+        self.config_spf = Some(
+            match field_result(&spf_check.findings, SpfFinding::is_invalid) {
+                DmarcFieldResult::BadConfig(findings)
+                    if spf_check.findings.iter().any(SpfFinding::is_very_bad) =>
+                {
+                    DmarcFieldResult::VeryBadConfig(findings)
                 }
+                result => result,
             },
+        );
+    }
+
+    /// Records the MX of the domain and classifies it as receiving mail or parked.
+    pub fn set_mx(&mut self, mx_check: &MxCheck, spf_check: &SpfCheck) {
+        let spf_lockdown = spf_check.record.as_ref().is_some_and(mx::is_spf_lockdown);
+
+        self.mx_records = Some(mx_check.to_string());
+        self.config_mx = Some(field_result(&mx_check.findings, MxFinding::is_invalid));
+        self.mail_class = Some(
+            MailClass::classify(
+                mx_check,
+                spf_lockdown,
+                self.applied_policy == Some(TagAction::Reject),
+            )
+            .to_string(),
+        );
+    }
+
+    /// Checks the controls of a domain that sends no mail and lists the records it lacks.
+    pub fn check_parked(&mut self, mx_check: &MxCheck, spf_check: &SpfCheck) {
+        let subdomain_policy = self.sp.as_ref().or(self.p.as_ref());
+
+        let parked_controls = ParkedControls {
+            dmarc_reject: self.applied_policy == Some(TagAction::Reject)
+                && subdomain_policy == Some(&TagAction::Reject),
+            spf_lockdown: spf_check.record.as_ref().is_some_and(mx::is_spf_lockdown),
+            null_mx: mx_check.is_null_mx(),
         };
 
-        match sp {
-            TagAction::None => match &self.p {
-                Some(p) => match p {
-                    TagAction::Reject => {
-                        DmarcFieldResult::VeryBadConfig(ERR_PERMITS_SUBDOMAIN_SPOOFING.to_string())
-                    }
-                    _ => DmarcFieldResult::BadConfig(ERR_IGNORE_SUBDOMAIN_DMARC_FAILS.to_string()),
+        let missing = parked_controls.missing_records(&self.domain_name);
+        let controls: Vec<&str> = missing.iter().map(|r| r.control()).collect();
+
+        self.parked_missing_records = Some(join(&missing, RAW_DATA_SEPARATOR));
+        self.config_parked = Some(match missing.is_empty() {
+            true => DmarcFieldResult::ValidConfig,
+            false => DmarcFieldResult::VeryBadConfig(format!(
+                "{} {}",
+                ERR_PARKED_MISSING,
+                controls.join(FINDING_SEPARATOR)
+            )),
+        });
+    }
+
+    /// Records the DNS lookups the SPF record costs receivers once every include is followed.
+    pub fn set_spf_expansion(&mut self, spf_expansion: &SpfExpansion) {
+        self.spf_lookups = Some(spf_expansion.lookups);
+        self.spf_void_lookups = Some(spf_expansion.void_lookups);
+        self.spf_include_tree = Some(spf_expansion.tree.to_string());
+
+        self.config_spf_lookups = Some(field_result(
+            &spf_expansion.findings,
+            ExpansionFinding::is_invalid,
+        ));
+    }
+
+    /// Records the DKIM selectors probed and those that have a key published.
+    pub fn set_dkim_selectors(&mut self, discovery: &SelectorDiscovery) {
+        self.dkim_selectors_probed = Some(discovery.probed);
+        self.dkim_selectors = Some(discovery.found.join(" "));
+    }
+
+    /// Records the strength of each DKIM key checked and what is wrong with them.
+    pub fn set_dkim_keys(&mut self, key_checks: &[DkimKeyCheck]) {
+        if key_checks.is_empty() {
+            return;
+        }
+
+        let findings: Vec<SelectorFinding> = key_checks
+            .iter()
+            .flat_map(|check| {
+                check.findings.iter().map(move |finding| SelectorFinding {
+                    selector: &check.selector,
+                    finding,
+                })
+            })
+            .collect();
+
+        self.dkim_keys = Some(join(key_checks, FINDING_SEPARATOR));
+        self.config_dkim = Some(field_result(&findings, |f| f.finding.is_invalid()));
+    }
+
+    /// Records the MTA-STS record and policy of the domain and what is wrong with them.
+    pub fn set_mta_sts(&mut self, mta_sts_check: &MtaStsCheck) {
+        self.mta_sts_id = mta_sts_check.id.clone();
+
+        if let Some(policy) = &mta_sts_check.policy {
+            self.mta_sts_mode = policy.mode.map(|mode| mode.to_string());
+            self.mta_sts_mx = Some(policy.mx.join(" "));
+            self.mta_sts_max_age = policy.max_age;
+        }
+
+        self.config_mta_sts = Some(field_result(
+            &mta_sts_check.findings,
+            MtaStsFinding::is_invalid,
+        ));
+    }
+
+    /// Records where the domain wants SMTP TLS reports sent and what is wrong with its record.
+    pub fn set_tls_rpt(&mut self, tls_rpt_check: &TlsRptCheck) {
+        self.tls_rpt_rua = Some(join(&tls_rpt_check.rua.uris, " "));
+
+        self.config_tls_rpt = Some(field_result(
+            &tls_rpt_check.findings,
+            TlsRptFinding::is_invalid,
+        ));
+    }
+
+    /// Records the BIMI logo of the domain and why receivers may not show it, which is most
+    /// often a DMARC policy below BIMI's requirement.
+    pub fn set_bimi(&mut self, mut bimi_check: BimiCheck) {
+        // Without a record there is no logo for the DMARC policy to hold back
+        if bimi_check.has_record() {
+            bimi_check.check_dmarc(
+                self.applied_policy
+                    .as_ref()
+                    .and_then(TagAction::enforcement),
+                match self.pct {
+                    Some(Percentage::Value(pct)) => pct,
+                    _ => MAX_PCT,
                 },
-                None => DmarcFieldResult::InvalidConfig(ERR_P_FLAG_MISSING.to_string()),
+                self.sp.as_ref().and_then(TagAction::enforcement),
+            );
+        }
+
+        self.bimi_logo = bimi_check.logo;
+        self.bimi_authority = bimi_check.evidence;
+
+        self.config_bimi = Some(field_result(&bimi_check.findings, BimiFinding::is_invalid));
+    }
+
+    /// Scores the domain from the `config_*` results of the checks run for every domain, to
+    /// be called once all checks ran. Results of optional checks are left out so a domain
+    /// gets the same grade whichever flags are passed.
+    pub fn set_score(&mut self, weights: &ScoreWeights) {
+        let config_results = [
+            &self.config_record_count,
+            &self.config_ignored_records,
+            &self.config_cname_chain,
+            &self.config_txt_strings,
+            &self.config_syntax,
+            &self.config_duplicate_tags,
+            &self.config_v_p_order,
+            &self.config_v,
+            &self.config_p,
+            &self.config_pct,
+            &self.config_sp,
+            &self.config_rua,
+            &self.config_ruf,
+            &self.config_report_auth,
+            &self.config_adkim,
+            &self.config_aspf,
+            &self.config_fo,
+            &self.config_rf,
+            &self.config_ri,
+            &self.config_np,
+            &self.config_psd,
+            &self.config_t,
+            &self.config_mx,
+            &self.config_spf,
+        ];
+
+        let score = score::score(
+            self.applied_policy.is_some(),
+            config_results.iter().filter_map(|c| c.as_ref()),
+            weights,
+        );
+
+        self.score = Some(score);
+        self.grade = Some(Grade::from_score(score));
+    }
+
+    /// Combines the applied policy with the SPF record and MX of the domain into a verdict
+    /// on whether mail claiming to be from it can be spoofed.
+    ///
+    /// `redirect_all` is the `all` qualifier reached by following the record's `redirect`.
+    pub fn set_spoofability(
+        &mut self,
+        spf_check: &SpfCheck,
+        redirect_all: Option<Qualifier>,
+        receives_mail: bool,
+    ) {
+        // A record receivers return permerror for protects nothing
+        let spf_record = spf_check
+            .record
+            .as_ref()
+            .filter(|record| record.errors.is_empty());
+
+        let spoofability = Spoofability::assess(&Exposure {
+            policy: self
+                .applied_policy
+                .as_ref()
+                .and_then(TagAction::enforcement),
+            pct: match self.pct {
+                Some(Percentage::Value(pct)) => pct,
+                _ => MAX_PCT,
             },
-            _ => DmarcFieldResult::ValidConfig,
+            testing: self.t == Some(TestingMode::Yes),
+            subdomain_policy: self
+                .sp
+                .as_ref()
+                .or(self.p.as_ref())
+                .and_then(TagAction::enforcement),
+            non_existent_policy: self.np.as_ref().and_then(TagAction::enforcement),
+            has_spf: spf_record.is_some(),
+            spf_all: spf_record.and_then(|record| record.all().or(redirect_all)),
+            spf_all_unknown: spf_record.is_some_and(|record| {
+                record.all().is_none() && record.redirect.is_some() && redirect_all.is_none()
+            }),
+            receives_mail,
+        });
+
+        self.spoofability = Some(spoofability.verdict.to_string());
+        self.spoofability_reasons = Some(spoofability.reasons.join(FINDING_SEPARATOR));
+    }
+
+    /// Whether a record was published for the domain. A CNAME chain that reaches no DMARC
+    /// record is reported but is not one, so the organizational domain is still queried.
+    pub fn has_record(&self) -> bool {
+        self.returned_record == YES && self.record_type != CNAME_RECORD
+    }
+
+    /// Domain the applied record was published for.
+    pub fn policy_domain(&self) -> &str {
+        self.policy_domain.as_ref().unwrap_or(&self.domain_name)
+    }
+
+    /// More than one record means no policy applies at all (RFC 7489 §6.6.3), so none of
+    /// the records are judged on their own and all of them are kept as raw data.
+    fn multiple(domain_name: &str, dmarc_records: Vec<DmarcRecordType>) -> Self {
+        let record_count = dmarc_records.len();
+        let mut record_types: Vec<String> = Vec::new();
+        let mut raw_data: Vec<String> = Vec::new();
+
+        for r in dmarc_records {
+            let dmarc = Self::new(domain_name, Some(r));
+
+            if !record_types.contains(&dmarc.record_type) {
+                record_types.push(dmarc.record_type);
+            }
+            raw_data.push(dmarc.raw_data);
+        }
+
+        Self {
+            domain_name: domain_name.to_string(),
+            returned_record: YES.to_string(),
+            record_type: record_types.join(" "),
+            record_count,
+            config_record_count: Some(DmarcFieldResult::InvalidConfig(format!(
+                "{} {}",
+                record_count, ERR_MULTIPLE_RECORDS
+            ))),
+            raw_data: raw_data.join(RAW_DATA_SEPARATOR),
+            ..Self::default()
         }
     }
-}
 
-#[derive(Debug, PartialEq)]
-struct DmarcParsed<'a> {
-    dmarc_entries: Option<Vec<DmarcEntry<'a>>>,
-    invalid_entries: Option<String>,
-    raw_txt: String,
+    /// Records where the policy was found and which of `p` or `sp` a receiver applies.
+    fn set_policy_domain(&mut self, policy_domain: &str) {
+        let is_fallback =
+            !policy_domain.eq_ignore_ascii_case(self.domain_name.trim_end_matches('.'));
+
+        let (applied_policy, applied_via) = match (&self.sp, &self.p) {
+            // Receivers discard a record with repeated tags
+            _ if self.config_duplicate_tags.is_some() => (None, P_TAG),
+            // and cannot read `v=DMARC1 p=reject` without the `;` as a policy
+            _ if self.policy_garbled => (None, P_TAG),
+            (Some(sp), _) if is_fallback => (Some(sp.clone()), SP_TAG),
+            (_, p) => (p.clone(), P_TAG),
+        };
+
+        self.policy_domain = Some(policy_domain.to_string());
+        self.applied_via = applied_policy.as_ref().map(|_| applied_via.to_string());
+        self.applied_policy = applied_policy;
+    }
+
+    /// Records the names a DMARCbis tree walk queried and the policy it settled on.
+    pub fn set_tree_walk(&mut self, tree_walk: &TreeWalk) {
+        self.tree_walk_queries = Some(tree_walk.queried.join(" "));
+
+        if let (Some(policy_domain), Some(record)) = (&tree_walk.policy_domain, &tree_walk.record) {
+            let mut picked = Self::new(
+                &self.domain_name,
+                Some(DmarcRecordType::Txt(Some(record.clone()))),
+            );
+            picked.set_policy_domain(policy_domain);
+
+            self.tree_walk_policy_domain = Some(policy_domain.clone());
+            self.tree_walk_applied_policy = picked.applied_policy;
+            self.tree_walk_applied_via = picked.applied_via;
+        }
+    }
+
+    pub fn set_organizational_domain(&mut self, organizational_domain: Option<String>) {
+        self.organizational_domain = organizational_domain;
+    }
+
+    pub fn new(domain_name: &str, dmarc_record: Option<DmarcRecordType>) -> Self {
+        let mut dmarc = Self {
+            domain_name: domain_name.to_string(),
+            ..Self::default()
+        };
+
+        if dmarc_record.is_some() {
+            dmarc.record_count = 1;
+            dmarc.config_record_count = Some(DmarcFieldResult::ValidConfig);
+        }
+
+        let dmarc_parsed = match dmarc_record {
+            Some(ref r) => match r {
+                DmarcRecordType::Cname(opt_url) => {
+                    dmarc.record_type = CNAME_RECORD.to_string();
+                    dmarc.returned_record = YES.to_string();
+                    if let Some(url) = opt_url {
+                        dmarc.raw_data = url.to_string();
+                    }
+                    return dmarc;
+                }
+                DmarcRecordType::Txt(ref opt_txt) => match opt_txt {
+                    Some(ref txt) => {
+                        dmarc.record_type = TXT_RECORD.to_string();
+                        dmarc.returned_record = YES.to_string();
+                        DmarcParsed::new(txt)
+                    }
+                    None => {
+                        dmarc.record_type = TXT_RECORD.to_string();
+                        dmarc.returned_record = YES.to_string();
+                        return dmarc;
+                    }
+                },
+                DmarcRecordType::Other => {
+                    dmarc.record_type = OTHER_RECORD.to_string();
+                    dmarc.returned_record = YES.to_string();
+                    return dmarc;
+                }
+            },
+            None => {
+                dmarc.returned_record = NO.to_string();
+                return dmarc;
+            }
+        };
+
+        dmarc.syntax_errors = dmarc_parsed.syntax_errors();
+        dmarc.config_syntax = Some(field_result(&dmarc_parsed.diagnostics, |_| true));
+        dmarc.policy_garbled = dmarc_parsed.policy_garbled;
+
+        let mut dmarc_entries = match dmarc_parsed.dmarc_entries {
+            Some(de) => de,
+            None => {
+                dmarc.raw_data = dmarc_parsed.raw_txt;
+                return dmarc;
+            }
+        };
+
+        let config_v_p_order = Self::check_v_and_p_order(&dmarc_entries);
+
+        let duplicate_tags = remove_duplicate_tags(&mut dmarc_entries);
+
+        if !duplicate_tags.is_empty() {
+            dmarc.config_duplicate_tags = Some(DmarcFieldResult::InvalidConfig(
+                duplicate_tags.join(FINDING_SEPARATOR),
+            ));
+        }
+
+        dmarc.v =
+            match_tag(V_TAG, &mut dmarc_entries).map(|v_entry| DmarcVersion::to_ver(v_entry.val));
+
+        dmarc.p =
+            match_tag(P_TAG, &mut dmarc_entries).map(|p_entry| TagAction::to_enum(p_entry.val));
+
+        dmarc.pct = match_tag(PCT_TAG, &mut dmarc_entries)
+            .map(|pct_entry| Percentage::to_pct(pct_entry.val));
+
+        dmarc.rua = match_tag(RUA_TAG, &mut dmarc_entries)
+            .map(|rua_entry| Self::to_uri_list(rua_entry.val));
+
+        dmarc.ruf = match_tag(RUF_TAG, &mut dmarc_entries)
+            .map(|ruf_entry| Self::to_uri_list(ruf_entry.val));
+
+        dmarc.sp =
+            match_tag(SP_TAG, &mut dmarc_entries).map(|sp_entry| TagAction::to_enum(sp_entry.val));
+
+        dmarc.adkim = match_tag(ADKIM_TAG, &mut dmarc_entries)
+            .map(|adkim_entry| AlignmentMode::to_enum(adkim_entry.val));
+
+        dmarc.aspf = match_tag(ASPF_TAG, &mut dmarc_entries)
+            .map(|aspf_entry| AlignmentMode::to_enum(aspf_entry.val));
+
+        dmarc.fo = match_tag(FO_TAG, &mut dmarc_entries)
+            .map(|fo_entry| FailureOptions::to_options(fo_entry.val));
+
+        dmarc.rf = match_tag(RF_TAG, &mut dmarc_entries)
+            .map(|rf_entry| ReportFormats::to_formats(rf_entry.val));
+
+        dmarc.ri = match_tag(RI_TAG, &mut dmarc_entries)
+            .map(|ri_entry| ReportInterval::to_interval(ri_entry.val));
+
+        dmarc.np =
+            match_tag(NP_TAG, &mut dmarc_entries).map(|np_entry| TagAction::to_enum(np_entry.val));
+
+        dmarc.psd = match_tag(PSD_TAG, &mut dmarc_entries)
+            .map(|psd_entry| PublicSuffixDomain::to_enum(psd_entry.val));
+
+        dmarc.t =
+            match_tag(T_TAG, &mut dmarc_entries).map(|t_entry| TestingMode::to_enum(t_entry.val));
+
+        let mut suggestions: Vec<String> = dmarc_entries
+            .iter()
+            .filter_map(|e| {
+                suggest(e.tag, KNOWN_TAGS)
+                    .map(|tag| format!("`{}=`: did you mean `{}`?", e.tag, tag))
+            })
+            .collect();
+        suggestions.extend(dmarc.value_suggestions());
+        dmarc.suggestions = match suggestions.is_empty() {
+            true => None,
+            false => Some(suggestions.join(FINDING_SEPARATOR)),
+        };
+
+        dmarc.others = match dmarc_entries.is_empty() {
+            true => None,
+            false => {
+                let mut others_tmp = String::new();
+                for e in dmarc_entries {
+                    others_tmp.push_str(&format!("{}={} ", e.tag, e.val));
+                }
+
+                Some(others_tmp)
+            }
+        };
+
+        dmarc.config_v_p_order = Some(config_v_p_order);
+        dmarc.config_v = Some(dmarc.check_v());
+        dmarc.config_p = Some(dmarc.check_p());
+        dmarc.config_pct = Some(dmarc.check_pct());
+        dmarc.config_sp = Some(dmarc.check_sp());
+        dmarc.config_rua = Some(Self::check_uris(&dmarc.rua));
+        dmarc.config_ruf = Some(Self::check_uris(&dmarc.ruf));
+        dmarc.config_adkim = Some(Self::check_alignment(&dmarc.adkim));
+        dmarc.config_aspf = Some(Self::check_alignment(&dmarc.aspf));
+        dmarc.config_fo = Some(dmarc.check_fo());
+        dmarc.config_rf = Some(dmarc.check_rf());
+        dmarc.config_ri = Some(dmarc.check_ri());
+        dmarc.config_np = Some(dmarc.check_np());
+        dmarc.config_psd = Some(dmarc.check_psd());
+        dmarc.config_t = Some(dmarc.check_t());
+        dmarc.raw_data = dmarc_parsed.raw_txt;
+
+        if let Some(rua) = &dmarc.rua {
+            dmarc.rua_addresses = Some(rua.addresses());
+            dmarc.rua_domains = Some(rua.hosts());
+            dmarc.rua_size_limits = Some(rua.size_limits());
+        }
+
+        if let Some(ruf) = &dmarc.ruf {
+            dmarc.ruf_addresses = Some(ruf.addresses());
+            dmarc.ruf_domains = Some(ruf.hosts());
+            dmarc.ruf_size_limits = Some(ruf.size_limits());
+        }
+
+        dmarc
+    }
+
+    /// Report destinations outside the Organizational Domain of the policy domain, which
+    /// need explicit authorization.
+    pub fn external_report_domains(&self, public_suffix_list: &PublicSuffixList) -> Vec<String> {
+        let mut domains: Vec<String> = Vec::new();
+        let policy_domain = self.policy_domain().trim_end_matches('.').to_lowercase();
+        let policy_org_domain = public_suffix_list.organizational_domain(&policy_domain);
+
+        // Destinations within the same Organizational Domain need no authorization, RFC 7489 §7.1
+        let is_internal = |host: &String| {
+            *host == policy_domain
+                || (policy_org_domain.is_some()
+                    && public_suffix_list.organizational_domain(host) == policy_org_domain)
+        };
+
+        for uri_list in [&self.rua, &self.ruf].iter().copied().flatten() {
+            for uri in uri_list.uris.iter() {
+                if is_internal(&uri.host) || domains.contains(&uri.host) {
+                    continue;
+                }
+                domains.push(uri.host.clone());
+            }
+        }
+
+        domains
+    }
+
+    pub fn set_report_authorizations(
+        &mut self,
+        report_authorizations: &[ReportAuthorization],
+        public_suffix_list: &PublicSuffixList,
+    ) {
+        let external_domains = self.external_report_domains(public_suffix_list);
+
+        if external_domains.is_empty() {
+            return;
+        }
+
+        let unauthorized: Vec<&String> = external_domains
+            .iter()
+            .filter(|d| {
+                !report_authorizations
+                    .iter()
+                    .any(|ra| &ra.target_domain == *d && ra.authorized)
+            })
+            .collect();
+
+        self.config_report_auth = Some(match unauthorized.is_empty() {
+            true => DmarcFieldResult::ValidConfig,
+            false => DmarcFieldResult::BadConfig(format!(
+                "{} {}",
+                ERR_UNAUTHORIZED_REPORT_DOMAINS,
+                join(&unauthorized, " ")
+            )),
+        });
+        self.external_report_domains = Some(external_domains.join(" "));
+    }
+
+    fn to_uri_list(uri_tag_val: &str) -> UriList {
+        let mut uri_list = UriList::parse_with_size_limits(uri_tag_val, &[MAILTO_SCHEME]);
+        uri_list.check_max_uris(MAX_DMARC_URIS);
+        uri_list
+    }
+
+    fn check_v(&self) -> DmarcFieldResult {
+        match &self.v {
+            Some(ver) => match ver {
+                DmarcVersion::Dmarc1 => DmarcFieldResult::ValidConfig,
+                DmarcVersion::Invalid(s) => DmarcFieldResult::InvalidConfig(s.clone()),
+            },
+            None => DmarcFieldResult::InvalidConfig(ERR_FLAG_NOT_PRESENT.to_string()),
+        }
+    }
+
+    fn check_p(&self) -> DmarcFieldResult {
+        match &self.p {
+            Some(v) => match v {
+                TagAction::Invalid(s) => DmarcFieldResult::InvalidConfig(s.clone()),
+                TagAction::None => DmarcFieldResult::VeryBadConfig(TAG_NONE.to_string()),
+                _ => DmarcFieldResult::ValidConfig,
+            },
+            None => DmarcFieldResult::InvalidConfig(ERR_FLAG_NOT_PRESENT.to_string()),
+        }
+    }
+
+    fn check_pct(&self) -> DmarcFieldResult {
+        match &self.pct {
+            Some(Percentage::Value(n)) => match &self.p {
+                Some(p) => match p {
+                    TagAction::None => {
+                        DmarcFieldResult::VeryBadConfig(ERR_POLICY_IS_NONE.to_string())
+                    }
+                    TagAction::Qurantine => {
+                        if *n < 25 {
+                            DmarcFieldResult::VeryBadConfig(n.to_string())
+                        } else if *n < MAX_PCT {
+                            DmarcFieldResult::BadConfig(n.to_string())
+                        } else {
+                            DmarcFieldResult::ValidConfig
+                        }
+                    }
+                    TagAction::Reject => DmarcFieldResult::ValidConfig,
+                    TagAction::Invalid(i) => DmarcFieldResult::InvalidConfig(i.clone()),
+                },
+                None => DmarcFieldResult::InvalidConfig(ERR_P_FLAG_MISSING.to_string()),
+            },
+            Some(Percentage::Invalid(s)) => {
+                DmarcFieldResult::InvalidConfig(format!("{} <- {}", s, ERR_PCT_NOT_PERCENTAGE))
+            }
+            None => DmarcFieldResult::ValidConfig,
+        }
+    }
+
+    fn check_uris(uri_list: &Option<UriList>) -> DmarcFieldResult {
+        let findings = match uri_list {
+            Some(uri_list) if !uri_list.findings.is_empty() => &uri_list.findings,
+            _ => return DmarcFieldResult::ValidConfig,
+        };
+
+        let findings_str = join(findings, FINDING_SEPARATOR);
+
+        match findings.iter().any(|f| f.is_invalid()) {
+            true => DmarcFieldResult::InvalidConfig(findings_str),
+            false => DmarcFieldResult::BadConfig(findings_str),
+        }
+    }
+
+    fn check_alignment(alignment: &Option<AlignmentMode>) -> DmarcFieldResult {
+        match alignment {
+            Some(AlignmentMode::Invalid(s)) => DmarcFieldResult::InvalidConfig(s.clone()),
+            _ => DmarcFieldResult::ValidConfig,
+        }
+    }
+
+    fn check_fo(&self) -> DmarcFieldResult {
+        let fo = match &self.fo {
+            Some(fo) => fo,
+            None => return DmarcFieldResult::ValidConfig,
+        };
+
+        let invalid: Vec<&FailureOption> =
+            fo.0.iter()
+                .filter(|o| matches!(o, FailureOption::Invalid(_)))
+                .collect();
+
+        if !invalid.is_empty() {
+            return DmarcFieldResult::InvalidConfig(join(&invalid, FO_SEPARATOR));
+        }
+
+        match self.ruf {
+            Some(_) => DmarcFieldResult::ValidConfig,
+            None => DmarcFieldResult::BadConfig(ERR_FO_WITHOUT_RUF.to_string()),
+        }
+    }
+
+    fn check_rf(&self) -> DmarcFieldResult {
+        let invalid: Vec<&ReportFormat> = match &self.rf {
+            Some(rf) => {
+                rf.0.iter()
+                    .filter(|f| matches!(f, ReportFormat::Invalid(_)))
+                    .collect()
+            }
+            None => return DmarcFieldResult::ValidConfig,
+        };
+
+        match invalid.is_empty() {
+            true => DmarcFieldResult::ValidConfig,
+            false => DmarcFieldResult::InvalidConfig(join(&invalid, RF_SEPARATOR)),
+        }
+    }
+
+    fn check_ri(&self) -> DmarcFieldResult {
+        match &self.ri {
+            Some(ReportInterval::Seconds(n)) if *n < MIN_RI => {
+                DmarcFieldResult::BadConfig(format!("{} <- {}", n, ERR_RI_TOO_SHORT))
+            }
+            Some(ReportInterval::Invalid(s)) => {
+                DmarcFieldResult::InvalidConfig(format!("{} <- {}", s, ERR_RI_NOT_NUMBER))
+            }
+            _ => DmarcFieldResult::ValidConfig,
+        }
+    }
+
+    fn check_np(&self) -> DmarcFieldResult {
+        match &self.np {
+            Some(TagAction::None) => DmarcFieldResult::BadConfig(ERR_NP_IS_NONE.to_string()),
+            Some(TagAction::Invalid(s)) => DmarcFieldResult::InvalidConfig(s.clone()),
+            _ => DmarcFieldResult::ValidConfig,
+        }
+    }
+
+    fn check_psd(&self) -> DmarcFieldResult {
+        match &self.psd {
+            Some(PublicSuffixDomain::Invalid(s)) => DmarcFieldResult::InvalidConfig(s.clone()),
+            _ => DmarcFieldResult::ValidConfig,
+        }
+    }
+
+    fn check_t(&self) -> DmarcFieldResult {
+        match &self.t {
+            Some(TestingMode::Yes) => DmarcFieldResult::BadConfig(ERR_TESTING_MODE.to_string()),
+            Some(TestingMode::Invalid(s)) => DmarcFieldResult::InvalidConfig(s.clone()),
+            _ => DmarcFieldResult::ValidConfig,
+        }
+    }
+
+    /// Flags tags the DMARCbis profile removes, receivers following it ignore them.
+    pub fn check_dmarcbis(&mut self) {
+        if !self.has_record() {
+            return;
+        }
+
+        let mut deprecated: Vec<&str> = Vec::new();
+
+        if self.pct.is_some() {
+            deprecated.push(PCT_TAG);
+        }
+        if self.rf.is_some() {
+            deprecated.push(RF_TAG);
+        }
+        if self.ri.is_some() {
+            deprecated.push(RI_TAG);
+        }
+
+        self.config_deprecated = Some(match deprecated.is_empty() {
+            true => DmarcFieldResult::ValidConfig,
+            false => DmarcFieldResult::BadConfig(format!(
+                "{}: {}",
+                ERR_DEPRECATED_TAGS,
+                deprecated.join(", ")
+            )),
+        });
+    }
+
+    fn check_v_and_p_order(dmarc_entries: &[DmarcEntry]) -> DmarcFieldResult {
+        if dmarc_entries.len() < 2 {
+            return DmarcFieldResult::InvalidConfig(ERR_MISSING_V_OR_P_FLAG.to_string());
+        }
+
+        let (v, p) = (&dmarc_entries[0], &dmarc_entries[1]);
+
+        if v.tag != V_TAG {
+            return DmarcFieldResult::InvalidConfig(ERR_FIRST_FLAG_NOT_V.to_string());
+        }
+
+        if p.tag != P_TAG {
+            return DmarcFieldResult::InvalidConfig(ERR_SECOND_FLAG_NOT_P.to_string());
+        }
+
+        DmarcFieldResult::ValidConfig
+    }
+
+    fn check_sp(&self) -> DmarcFieldResult {
+        let sp = match &self.sp {
+            Some(sp) => sp,
+            None => match &self.p {
+                Some(p) => p,
+                None => {
+                    return DmarcFieldResult::InvalidConfig(
+                        ERR_P_FLAG_MISSING_AND_SP_NOT_SET.to_string(),
+                    )
+                }
+            },
+        };
+
+        match sp {
+            TagAction::None => match &self.p {
+                Some(p) => match p {
+                    TagAction::Reject => {
+                        DmarcFieldResult::VeryBadConfig(ERR_PERMITS_SUBDOMAIN_SPOOFING.to_string())
+                    }
+                    _ => DmarcFieldResult::BadConfig(ERR_IGNORE_SUBDOMAIN_DMARC_FAILS.to_string()),
+                },
+                None => DmarcFieldResult::InvalidConfig(ERR_P_FLAG_MISSING.to_string()),
+            },
+            _ => DmarcFieldResult::ValidConfig,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct DmarcParsed<'a> {
+    dmarc_entries: Option<Vec<DmarcEntry<'a>>>,
+    diagnostics: Vec<Diagnostic>,
+    /// Whether a missing `;` or `=` blurs the `v` or `p` tag.
+    policy_garbled: bool,
+    raw_txt: String,
+}
+
+impl<'a> DmarcParsed<'a> {
+    fn new(txt: &'a str) -> Self {
+        let raw_txt_quoted = format!("\"{}\"", txt);
+
+        let mut tag_list = TagList::parse(txt);
+
+        if !tag_list.tags.is_empty() {
+            tag_list.check_version_first(V_TAG);
+        }
+
+        let dmarc_entries_opt = match tag_list.tags.is_empty() {
+            true => None,
+            false => Some(
+                tag_list
+                    .tags
+                    .iter()
+                    .map(|t| DmarcEntry::new(t.name, t.value))
+                    .collect(),
+            ),
+        };
+
+        Self {
+            dmarc_entries: dmarc_entries_opt,
+            policy_garbled: tag_list.is_garbled(V_TAG) || tag_list.is_garbled(P_TAG),
+            diagnostics: tag_list.diagnostics,
+            raw_txt: raw_txt_quoted,
+        }
+    }
+
+    fn syntax_errors(&self) -> Option<String> {
+        match self.diagnostics.is_empty() {
+            true => None,
+            false => Some(
+                self.diagnostics
+                    .iter()
+                    .map(|d| d.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            ),
+        }
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum DmarcFieldResult {
+    ValidConfig,
+    BadConfig(String),
+    VeryBadConfig(String),
+    InvalidConfig(String),
+}
+
+impl fmt::Display for DmarcFieldResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ValidConfig => write!(f, "Valid"),
+            Self::BadConfig(s) => write!(f, "Bad: {}", s),
+            Self::VeryBadConfig(s) => write!(f, "Very bad: {}", s),
+            Self::InvalidConfig(s) => write!(f, "Invalid: {}", s),
+        }
+    }
+}
+
+impl Serialize for DmarcFieldResult {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[test]
+fn dmarc_parsed_new() {
+    assert_eq!(
+        DmarcParsed::new(""),
+        DmarcParsed {
+            dmarc_entries: None,
+            diagnostics: vec![],
+            policy_garbled: false,
+            raw_txt: "\"\"".to_string()
+        }
+    );
+
+    let single_entry = format!("{}={}", V_TAG, DMARC1);
+    assert_eq!(
+        DmarcParsed::new(&single_entry),
+        DmarcParsed {
+            dmarc_entries: Some(vec![DmarcEntry::new(V_TAG, DMARC1)]),
+            diagnostics: vec![],
+            policy_garbled: false,
+            raw_txt: format!("\"{}\"", single_entry),
+        }
+    );
+
+    let missing_separator = format!("{}={} {}={}", V_TAG, DMARC1, P_TAG, TAG_NONE);
+    let dmarc_parsed = DmarcParsed::new(&missing_separator);
+    assert_eq!(
+        dmarc_parsed.dmarc_entries,
+        Some(vec![
+            DmarcEntry::new(V_TAG, DMARC1),
+            DmarcEntry::new(P_TAG, TAG_NONE)
+        ])
+    );
+    assert_eq!(
+        dmarc_parsed.syntax_errors(),
+        Some("Missing `;` before `p` at 9..10".to_string())
+    );
+    assert!(dmarc_parsed.policy_garbled);
+
+    let invalid_tag = "p%fsa";
+    let invalid_entry = format!("{}={};{};", V_TAG, DMARC1, invalid_tag);
+    let dmarc_parsed = DmarcParsed::new(&invalid_entry);
+    assert_eq!(
+        dmarc_parsed.dmarc_entries,
+        Some(vec![DmarcEntry::new(V_TAG, DMARC1)])
+    );
+    assert_eq!(
+        dmarc_parsed.syntax_errors(),
+        Some(format!("Missing `=` in `{}` at 9..14", invalid_tag))
+    );
+
+    let v_not_first = format!("{}={}; {}={}", P_TAG, TAG_NONE, V_TAG, DMARC1);
+    assert_eq!(
+        DmarcParsed::new(&v_not_first).syntax_errors(),
+        Some("Version tag is not first at 8..16".to_string())
+    );
+
+    let valid_entry = format!("{}={}; {}={};", V_TAG, DMARC1, P_TAG, TAG_NONE);
+    assert_eq!(
+        DmarcParsed::new(&valid_entry),
+        DmarcParsed {
+            dmarc_entries: Some(vec![
+                DmarcEntry::new(V_TAG, DMARC1),
+                DmarcEntry::new(P_TAG, TAG_NONE)
+            ]),
+            diagnostics: vec![],
+            policy_garbled: false,
+            raw_txt: format!("\"{}\"", valid_entry),
+        }
+    );
+}
+
+// Tests
+#[test]
+fn dmarc_version_to_ver() {
+    assert_eq!(
+        DmarcVersion::Invalid("blah".to_string()),
+        DmarcVersion::to_ver("blah")
+    );
+
+    assert_eq!(
+        DmarcVersion::Invalid("dmarc1".to_string()),
+        DmarcVersion::to_ver("dmarc1")
+    );
+
+    assert_eq!(DmarcVersion::Dmarc1, DmarcVersion::to_ver(DMARC1));
+}
+
+#[test]
+#[allow(clippy::field_reassign_with_default)]
+fn dmarc_check_v() {
+    let mut dmarc = Dmarc::default();
+
+    dmarc.v = None;
+    assert_eq!(
+        DmarcFieldResult::InvalidConfig(ERR_FLAG_NOT_PRESENT.to_string()),
+        dmarc.check_v()
+    );
+
+    let invalid_ver = "smark";
+    dmarc.v = Some(DmarcVersion::Invalid(invalid_ver.to_string()));
+    assert_eq!(
+        DmarcFieldResult::InvalidConfig(invalid_ver.to_string()),
+        dmarc.check_v()
+    );
+
+    dmarc.v = Some(DmarcVersion::Dmarc1);
+    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_v());
+}
+
+#[test]
+#[allow(clippy::field_reassign_with_default)]
+fn dmarc_check_p() {
+    let mut dmarc = Dmarc::default();
+
+    dmarc.p = None;
+    assert_eq!(
+        DmarcFieldResult::InvalidConfig(ERR_FLAG_NOT_PRESENT.to_string()),
+        dmarc.check_p()
+    );
+
+    dmarc.p = Some(TagAction::None);
+    assert_eq!(
+        DmarcFieldResult::VeryBadConfig(TAG_NONE.to_string()),
+        dmarc.check_p()
+    );
+
+    dmarc.p = Some(TagAction::Qurantine);
+    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_p());
+
+    dmarc.p = Some(TagAction::Reject);
+    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_p());
+}
+
+#[test]
+#[allow(clippy::field_reassign_with_default)]
+fn dmarc_check_pct() {
+    let mut dmarc = Dmarc::default();
+
+    dmarc.pct = None;
+    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_pct());
+
+    let not_number = "a".to_string();
+    let zero_pct = "0".to_string();
+    let twenty_six_pct = "26".to_string();
+    let one_hundred_pct = "100".to_string();
+    let two_hundred_pct = "200".to_string();
+
+    dmarc.pct = Some(Percentage::to_pct(&not_number));
+    assert_eq!(
+        DmarcFieldResult::InvalidConfig(format!("{} <- {}", not_number, ERR_PCT_NOT_PERCENTAGE)),
+        dmarc.check_pct()
+    );
+
+    dmarc.pct = Some(Percentage::to_pct(&zero_pct));
+    assert_eq!(
+        DmarcFieldResult::InvalidConfig(ERR_P_FLAG_MISSING.to_string()),
+        dmarc.check_pct()
+    );
+
+    dmarc.p = Some(TagAction::None);
+    assert_eq!(
+        DmarcFieldResult::VeryBadConfig(ERR_POLICY_IS_NONE.to_string()),
+        dmarc.check_pct()
+    );
+
+    dmarc.p = Some(TagAction::Qurantine);
+    dmarc.pct = Some(Percentage::to_pct(&zero_pct));
+    assert_eq!(
+        DmarcFieldResult::VeryBadConfig(zero_pct.clone()),
+        dmarc.check_pct()
+    );
+
+    dmarc.pct = Some(Percentage::to_pct(&twenty_six_pct));
+    assert_eq!(
+        DmarcFieldResult::BadConfig(twenty_six_pct.clone()),
+        dmarc.check_pct()
+    );
+
+    dmarc.pct = Some(Percentage::to_pct(&one_hundred_pct));
+    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_pct());
+
+    dmarc.pct = Some(Percentage::to_pct(&two_hundred_pct));
+    assert_eq!(
+        DmarcFieldResult::InvalidConfig(format!(
+            "{} <- {}",
+            two_hundred_pct, ERR_PCT_NOT_PERCENTAGE
+        )),
+        dmarc.check_pct()
+    );
+
+    dmarc.p = Some(TagAction::Reject);
+    dmarc.pct = Some(Percentage::to_pct(&two_hundred_pct));
+    assert_eq!(
+        DmarcFieldResult::InvalidConfig(format!(
+            "{} <- {}",
+            two_hundred_pct, ERR_PCT_NOT_PERCENTAGE
+        )),
+        dmarc.check_pct()
+    );
+
+    dmarc.pct = Some(Percentage::to_pct(&twenty_six_pct));
+    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_pct());
+}
+
+#[test]
+#[allow(clippy::field_reassign_with_default)]
+fn dmarc_check_sp() {
+    let mut dmarc = Dmarc::default();
+
+    dmarc.p = None;
+    dmarc.sp = None;
+    assert_eq!(
+        DmarcFieldResult::InvalidConfig(ERR_P_FLAG_MISSING_AND_SP_NOT_SET.to_string()),
+        dmarc.check_sp()
+    );
+
+    dmarc.sp = Some(TagAction::None);
+    assert_eq!(
+        DmarcFieldResult::InvalidConfig(ERR_P_FLAG_MISSING.to_string()),
+        dmarc.check_sp()
+    );
+
+    dmarc.p = Some(TagAction::None);
+    assert_eq!(
+        DmarcFieldResult::BadConfig(ERR_IGNORE_SUBDOMAIN_DMARC_FAILS.to_string()),
+        dmarc.check_sp()
+    );
+
+    dmarc.p = Some(TagAction::Reject);
+    assert_eq!(
+        DmarcFieldResult::VeryBadConfig(ERR_PERMITS_SUBDOMAIN_SPOOFING.to_string()),
+        dmarc.check_sp()
+    );
+
+    dmarc.p = Some(TagAction::Qurantine);
+    assert_eq!(
+        DmarcFieldResult::BadConfig(ERR_IGNORE_SUBDOMAIN_DMARC_FAILS.to_string()),
+        dmarc.check_sp()
+    );
+
+    dmarc.sp = Some(TagAction::Reject);
+    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_sp());
+
+    dmarc.sp = Some(TagAction::Qurantine);
+    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_sp());
+}
+
+#[test]
+fn dmarc_check_v_and_p_order() {
+    let mut dmarc_entries: Vec<DmarcEntry> = Vec::new();
+
+    assert_eq!(
+        Dmarc::check_v_and_p_order(&dmarc_entries),
+        DmarcFieldResult::InvalidConfig(ERR_MISSING_V_OR_P_FLAG.to_string()),
+    );
+
+    dmarc_entries.push(DmarcEntry::new("A", DMARC1));
+    assert_eq!(
+        Dmarc::check_v_and_p_order(&dmarc_entries),
+        DmarcFieldResult::InvalidConfig(ERR_MISSING_V_OR_P_FLAG.to_string()),
+    );
+
+    dmarc_entries.push(DmarcEntry::new("B", TAG_NONE));
+    assert_eq!(
+        Dmarc::check_v_and_p_order(&dmarc_entries),
+        DmarcFieldResult::InvalidConfig(ERR_FIRST_FLAG_NOT_V.to_string()),
+    );
+
+    dmarc_entries[0] = DmarcEntry::new(V_TAG, DMARC1);
+    assert_eq!(
+        Dmarc::check_v_and_p_order(&dmarc_entries),
+        DmarcFieldResult::InvalidConfig(ERR_SECOND_FLAG_NOT_P.to_string()),
+    );
+
+    dmarc_entries[1] = DmarcEntry::new(P_TAG, TAG_NONE);
+    assert_eq!(
+        Dmarc::check_v_and_p_order(&dmarc_entries),
+        DmarcFieldResult::ValidConfig,
+    );
+}
+
+#[test]
+fn percentage_to_pct() {
+    assert_eq!(Percentage::to_pct("0"), Percentage::Value(0));
+    assert_eq!(Percentage::to_pct("100"), Percentage::Value(100));
+    assert_eq!(
+        Percentage::to_pct("101"),
+        Percentage::Invalid("101".to_string())
+    );
+    assert_eq!(
+        Percentage::to_pct("-1"),
+        Percentage::Invalid("-1".to_string())
+    );
+}
+
+#[test]
+fn failure_options_to_options() {
+    assert_eq!(
+        FailureOptions::to_options("0:1:d:s:d"),
+        FailureOptions(vec![
+            FailureOption::AllFail,
+            FailureOption::AnyFail,
+            FailureOption::Dkim,
+            FailureOption::Spf
+        ])
+    );
+
+    assert_eq!(
+        FailureOptions::to_options("D:S"),
+        FailureOptions(vec![FailureOption::Dkim, FailureOption::Spf])
+    );
+
+    let fo = FailureOptions::to_options("1 : x");
+    assert!(fo.0.contains(&FailureOption::AnyFail));
+    assert!(fo.0.contains(&FailureOption::Invalid("x".to_string())));
+}
+
+#[test]
+#[allow(clippy::field_reassign_with_default)]
+fn dmarc_check_alignment_fo_rf_ri() {
+    let mut dmarc = Dmarc::default();
+
+    assert_eq!(Dmarc::check_alignment(&None), DmarcFieldResult::ValidConfig);
+    assert_eq!(
+        Dmarc::check_alignment(&Some(AlignmentMode::to_enum(ALIGNMENT_STRICT))),
+        DmarcFieldResult::ValidConfig
+    );
+    assert_eq!(AlignmentMode::to_enum("S"), AlignmentMode::Strict);
+    assert_eq!(AlignmentMode::to_enum("R"), AlignmentMode::Relaxed);
+    assert_eq!(
+        Dmarc::check_alignment(&Some(AlignmentMode::to_enum("strict"))),
+        DmarcFieldResult::InvalidConfig("strict".to_string())
+    );
+
+    dmarc.fo = Some(FailureOptions::to_options("1:x:y"));
+    assert_eq!(
+        dmarc.check_fo(),
+        DmarcFieldResult::InvalidConfig("x:y".to_string())
+    );
+
+    dmarc.fo = Some(FailureOptions::to_options("1"));
+    assert_eq!(
+        dmarc.check_fo(),
+        DmarcFieldResult::BadConfig(ERR_FO_WITHOUT_RUF.to_string())
+    );
+
+    dmarc.ruf = Some(Dmarc::to_uri_list("mailto:ruf@example.com"));
+    assert_eq!(dmarc.check_fo(), DmarcFieldResult::ValidConfig);
+
+    dmarc.rf = Some(ReportFormats::to_formats("AFRF:iodef"));
+    assert_eq!(
+        dmarc.check_rf(),
+        DmarcFieldResult::InvalidConfig("iodef".to_string())
+    );
+
+    dmarc.ri = Some(ReportInterval::to_interval("86400"));
+    assert_eq!(dmarc.check_ri(), DmarcFieldResult::ValidConfig);
+
+    dmarc.ri = Some(ReportInterval::to_interval("60"));
+    assert_eq!(
+        dmarc.check_ri(),
+        DmarcFieldResult::BadConfig(format!("60 <- {}", ERR_RI_TOO_SHORT))
+    );
+
+    dmarc.ri = Some(ReportInterval::to_interval("daily"));
+    assert_eq!(
+        dmarc.check_ri(),
+        DmarcFieldResult::InvalidConfig(format!("daily <- {}", ERR_RI_NOT_NUMBER))
+    );
 }
 
-impl<'a> DmarcParsed<'a> {
-    fn new(txt: &'a String) -> Self {
-        let raw_txt_quoted = format!("\"{}\"", txt.clone());
-
-        if txt.is_empty() {
-            return Self {
-                dmarc_entries: None,
-                invalid_entries: None,
-                raw_txt: raw_txt_quoted,
-            };
-        }
-
-        let mut dmarc_entries: Vec<DmarcEntry> = Vec::new();
-        let mut invalid_entries = String::new();
-        let entry_iter = txt.split(';');
-
-        if entry_iter.clone().count() > 1 {
-            for e in entry_iter {
-                match e.find('=') {
-                    Some(idx) => dmarc_entries.push(DmarcEntry::new(
-                        &e[0..idx].trim(),
-                        &e[idx + 1..e.len()].trim(),
-                    )),
-                    None => {
-                        if !e.is_empty() {
-                            invalid_entries.push_str(e)
-                        }
-                    }
-                }
-            }
-        }
+#[test]
+fn dmarc_check_uris() {
+    assert_eq!(Dmarc::check_uris(&None), DmarcFieldResult::ValidConfig);
 
-        let invalid_entries_opt = match invalid_entries.is_empty() {
-            true => None,
-            false => Some(invalid_entries),
-        };
+    let rua = Dmarc::to_uri_list("mailto:a@example.com, mailto:b@example.com");
+    assert_eq!(Dmarc::check_uris(&Some(rua)), DmarcFieldResult::ValidConfig);
 
-        let dmarc_entries_opt = match dmarc_entries.is_empty() {
-            true => None,
-            false => Some(dmarc_entries),
-        };
+    let rua =
+        Dmarc::to_uri_list("mailto:a@example.com, mailto:b@example.com, mailto:c@example.com");
+    assert_eq!(
+        Dmarc::check_uris(&Some(rua)),
+        DmarcFieldResult::BadConfig(
+            "3 URIs, receivers are only required to use the first 2".to_string()
+        )
+    );
 
-        Self {
-            dmarc_entries: dmarc_entries_opt,
-            invalid_entries: invalid_entries_opt,
-            raw_txt: raw_txt_quoted,
-        }
-    }
+    let rua = Dmarc::to_uri_list("mailto:a@example.com!1x, http://example.com");
+    assert_eq!(
+        Dmarc::check_uris(&Some(rua)),
+        DmarcFieldResult::InvalidConfig(
+            "Bad size limit `mailto:a@example.com!1x`, Unsupported URI scheme `http://example.com`"
+                .to_string()
+        )
+    );
 }
 
-#[derive(Debug, PartialEq)]
-pub enum DmarcFieldResult {
-    ValidConfig,
-    BadConfig(String),
-    VeryBadConfig(String),
-    InvalidConfig(String),
-    Empty,
+#[cfg(test)]
+pub(crate) fn txt_record(name: &str, txt: &str) -> Record {
+    use trust_dns_client::rr::{rdata::TXT, Name, RData};
+
+    Record::from_rdata(
+        Name::from_utf8(name).unwrap(),
+        300,
+        RData::TXT(TXT::new(vec![txt.to_string()])),
+    )
 }
 
-impl ToString for DmarcFieldResult {
-    fn to_string(&self) -> String {
-        match self {
-            Self::ValidConfig => "Valid".to_string(),
-            Self::BadConfig(s) => format!("Bad: {}", s),
-            Self::VeryBadConfig(s) => format!("Very bad: {}", s),
-            Self::InvalidConfig(s) => format!("Invalid: {}", s),
-            Self::Empty => "Empty".to_string(),
-        }
-    }
+#[test]
+fn report_authorization_new() {
+    let auth_name = "example.com._report._dmarc.vendor.net";
+
+    assert!(!ReportAuthorization::new("vendor.net", &[]).authorized);
+    assert!(
+        ReportAuthorization::new("vendor.net", &[txt_record(auth_name, "v=DMARC1")]).authorized
+    );
+    assert!(
+        !ReportAuthorization::new("vendor.net", &[txt_record(auth_name, "v=spf1 -all")]).authorized
+    );
 }
 
 #[test]
-fn dmarc_parsed_new() {
-    assert_eq!(
-        DmarcParsed::new(&"".to_string()),
-        DmarcParsed {
-            dmarc_entries: None,
-            invalid_entries: None,
-            raw_txt: "\"\"".to_string()
-        }
-    );
+fn dmarc_set_report_authorizations() {
+    let raw_txt = "v=DMARC1; p=reject; rua=mailto:d@example.com,mailto:d@vendor.net; ruf=mailto:f@Other.org,mailto:f@mail.example.com".to_string();
+    let mut dmarc = Dmarc::new("example.com", Some(DmarcRecordType::Txt(Some(raw_txt))));
 
-    let invalid_entry = format!("{}={} {}={}", V_TAG, DMARC1, P_TAG, TAG_NONE);
+    let public_suffix_list = PublicSuffixList::bundled();
     assert_eq!(
-        DmarcParsed::new(&invalid_entry),
-        DmarcParsed {
-            dmarc_entries: None,
-            invalid_entries: None,
-            raw_txt: format!("\"{}\"", invalid_entry),
-        }
+        dmarc.external_report_domains(&public_suffix_list),
+        vec!["vendor.net".to_string(), "other.org".to_string()]
     );
 
-    let invalid_tag = "p%fsa";
-    let invalid_entry = format!("{}={};{};", V_TAG, DMARC1, invalid_tag);
+    dmarc.set_report_authorizations(
+        &[
+            ReportAuthorization::new(
+                "vendor.net",
+                &[txt_record(
+                    "example.com._report._dmarc.vendor.net",
+                    "v=DMARC1;",
+                )],
+            ),
+            ReportAuthorization::new("other.org", &[]),
+        ],
+        &public_suffix_list,
+    );
     assert_eq!(
-        DmarcParsed::new(&invalid_entry),
-        DmarcParsed {
-            dmarc_entries: Some(vec![DmarcEntry::new(V_TAG, DMARC1)]),
-            invalid_entries: Some(invalid_tag.to_string()),
-            raw_txt: format!("\"{}\"", invalid_entry),
-        }
+        dmarc.external_report_domains,
+        Some("vendor.net other.org".to_string())
     );
-
-    let valid_entry = format!("{}={}; {}={};", V_TAG, DMARC1, P_TAG, TAG_NONE);
     assert_eq!(
-        DmarcParsed::new(&valid_entry),
-        DmarcParsed {
-            dmarc_entries: Some(vec![
-                DmarcEntry::new(V_TAG, DMARC1),
-                DmarcEntry::new(P_TAG, TAG_NONE)
-            ]),
-            invalid_entries: None,
-            raw_txt: format!("\"{}\"", valid_entry),
-        }
+        dmarc.config_report_auth,
+        Some(DmarcFieldResult::BadConfig(format!(
+            "{} other.org",
+            ERR_UNAUTHORIZED_REPORT_DOMAINS
+        )))
     );
 }
 
-// Tests
 #[test]
-fn dmarc_version_to_ver() {
+fn dmarc_external_report_domains_organizational() {
+    let public_suffix_list = PublicSuffixList::bundled();
+
+    // A subdomain reporting to its parent stays within its Organizational Domain
+    let raw_txt = "v=DMARC1; p=reject; rua=mailto:d@example.com".to_string();
+    let dmarc = Dmarc::new("sub.example.com", Some(DmarcRecordType::Txt(Some(raw_txt))));
     assert_eq!(
-        DmarcVersion::Invalid("blah".to_string()),
-        DmarcVersion::to_ver("blah")
+        dmarc.external_report_domains(&public_suffix_list),
+        Vec::<String>::new()
     );
 
+    let raw_txt = "v=DMARC1; p=reject; rua=mailto:d@other.co.uk,mailto:d@example.co.uk".to_string();
+    let dmarc = Dmarc::new(
+        "mail.example.co.uk",
+        Some(DmarcRecordType::Txt(Some(raw_txt))),
+    );
     assert_eq!(
-        DmarcVersion::Invalid("dmarc1".to_string()),
-        DmarcVersion::to_ver("dmarc1")
+        dmarc.external_report_domains(&public_suffix_list),
+        vec!["other.co.uk".to_string()]
     );
+}
 
-    assert_eq!(DmarcVersion::Dmarc1, DmarcVersion::to_ver(DMARC1));
+#[test]
+fn dmarc_from_answers_policy_domain() {
+    let dmarc = Dmarc::from_answers("mail.example.co.uk", "example.co.uk", &[]);
+    assert_eq!(dmarc.returned_record, NO.to_string());
+    assert_eq!(dmarc.record_count, 0);
+    assert_eq!(dmarc.policy_domain, None);
+
+    let dmarc = Dmarc::from_answers(
+        "mail.example.co.uk",
+        "example.co.uk",
+        &[txt_record(
+            "_dmarc.example.co.uk",
+            "v=DMARC1; p=reject; sp=quarantine",
+        )],
+    );
+    assert_eq!(dmarc.policy_domain, Some("example.co.uk".to_string()));
+    assert_eq!(dmarc.applied_policy, Some(TagAction::Qurantine));
+    assert_eq!(dmarc.applied_via, Some(SP_TAG.to_string()));
+
+    let dmarc = Dmarc::from_answers(
+        "example.co.uk",
+        "example.co.uk",
+        &[txt_record(
+            "_dmarc.example.co.uk",
+            "v=DMARC1; p=reject; sp=quarantine",
+        )],
+    );
+    assert_eq!(dmarc.applied_policy, Some(TagAction::Reject));
+    assert_eq!(dmarc.applied_via, Some(P_TAG.to_string()));
+    assert_eq!(dmarc.record_count, 1);
 }
 
 #[test]
-fn dmarc_check_v() {
-    let mut dmarc = Dmarc::default();
+fn dmarc_from_answers_multiple() {
+    let dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[
+            txt_record("_dmarc.example.com", "v=DMARC1; p=reject"),
+            txt_record("_dmarc.example.com", "v=DMARC1; p=none"),
+        ],
+    );
 
-    dmarc.v = None;
+    assert_eq!(dmarc.returned_record, YES.to_string());
+    assert_eq!(dmarc.record_type, TXT_RECORD.to_string());
+    assert_eq!(dmarc.record_count, 2);
     assert_eq!(
-        DmarcFieldResult::InvalidConfig(ERR_FLAG_NOT_PRESENT.to_string()),
-        dmarc.check_v()
+        dmarc.config_record_count,
+        Some(DmarcFieldResult::InvalidConfig(format!(
+            "2 {}",
+            ERR_MULTIPLE_RECORDS
+        )))
     );
-
-    let invalid_ver = "smark";
-    dmarc.v = Some(DmarcVersion::Invalid(invalid_ver.to_string()));
+    assert_eq!(dmarc.p, None);
+    assert_eq!(dmarc.applied_policy, None);
     assert_eq!(
-        DmarcFieldResult::InvalidConfig(invalid_ver.to_string()),
-        dmarc.check_v()
+        dmarc.raw_data,
+        "\"v=DMARC1; p=reject\" | \"v=DMARC1; p=none\""
     );
+}
 
-    dmarc.v = Some(DmarcVersion::Dmarc1);
-    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_v());
+#[test]
+fn dmarc_set_tree_walk() {
+    let mut dmarc = Dmarc::new("a.b.example.com", None);
+    dmarc.set_tree_walk(&TreeWalk {
+        queried: vec!["a.b.example.com".to_string(), "b.example.com".to_string()],
+        policy_domain: Some("b.example.com".to_string()),
+        record: Some("v=DMARC1; p=reject; sp=none".to_string()),
+    });
+
+    assert_eq!(
+        dmarc.tree_walk_queries,
+        Some("a.b.example.com b.example.com".to_string())
+    );
+    assert_eq!(
+        dmarc.tree_walk_policy_domain,
+        Some("b.example.com".to_string())
+    );
+    assert_eq!(dmarc.tree_walk_applied_policy, Some(TagAction::None));
+    assert_eq!(dmarc.tree_walk_applied_via, Some(SP_TAG.to_string()));
 }
 
 #[test]
-fn dmarc_check_p() {
-    let mut dmarc = Dmarc::default();
+fn dmarc_dmarcbis_tags() {
+    let raw_txt = "v=DMARC1; p=reject; np=none; psd=x; t=y; pct=50; ri=3600".to_string();
+    let mut dmarc = Dmarc::new("example.com", Some(DmarcRecordType::Txt(Some(raw_txt))));
 
-    dmarc.p = None;
+    assert_eq!(dmarc.np, Some(TagAction::None));
     assert_eq!(
-        DmarcFieldResult::InvalidConfig(ERR_FLAG_NOT_PRESENT.to_string()),
-        dmarc.check_p()
+        dmarc.check_np(),
+        DmarcFieldResult::BadConfig(ERR_NP_IS_NONE.to_string())
+    );
+    assert_eq!(
+        dmarc.check_psd(),
+        DmarcFieldResult::InvalidConfig("x".to_string())
+    );
+    assert_eq!(
+        dmarc.check_t(),
+        DmarcFieldResult::BadConfig(ERR_TESTING_MODE.to_string())
     );
+    assert_eq!(dmarc.config_deprecated, None);
 
-    dmarc.p = Some(TagAction::None);
+    dmarc.check_dmarcbis();
     assert_eq!(
-        DmarcFieldResult::VeryBadConfig(TAG_NONE.to_string()),
-        dmarc.check_p()
+        dmarc.config_deprecated,
+        Some(DmarcFieldResult::BadConfig(format!(
+            "{}: pct, ri",
+            ERR_DEPRECATED_TAGS
+        )))
     );
 
-    dmarc.p = Some(TagAction::Qurantine);
-    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_p());
+    let raw_txt = "v=DMARC1; p=reject; psd=n; t=n".to_string();
+    let mut dmarc = Dmarc::new("example.com", Some(DmarcRecordType::Txt(Some(raw_txt))));
+    assert_eq!(dmarc.psd, Some(PublicSuffixDomain::No));
+    assert_eq!(dmarc.check_t(), DmarcFieldResult::ValidConfig);
 
-    dmarc.p = Some(TagAction::Reject);
-    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_p());
+    dmarc.check_dmarcbis();
+    assert_eq!(dmarc.config_deprecated, Some(DmarcFieldResult::ValidConfig));
+    assert_eq!(dmarc.others, None);
 }
 
 #[test]
-fn dmarc_check_pct() {
-    let mut dmarc = Dmarc::default();
+fn dmarc_from_answers_ignored_records() {
+    let dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[
+            txt_record("_dmarc.example.com", "v=spf1 -all"),
+            txt_record("_dmarc.example.com", "v=DMARC1; p=reject"),
+            txt_record("_dmarc.example.com", "google-site-verification=abc"),
+        ],
+    );
 
-    dmarc.pct = None;
-    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_pct());
+    assert_eq!(dmarc.record_count, 1);
+    assert_eq!(dmarc.p, Some(TagAction::Reject));
+    assert_eq!(
+        dmarc.ignored_records,
+        Some("\"v=spf1 -all\" | \"google-site-verification=abc\"".to_string())
+    );
+    assert_eq!(
+        dmarc.config_ignored_records,
+        Some(DmarcFieldResult::BadConfig(format!(
+            "2 {}",
+            ERR_IGNORED_RECORDS
+        )))
+    );
 
-    let not_number = "a".to_string();
-    let zero_pct = "0".to_string();
-    let twenty_six_pct = "26".to_string();
-    let one_hundred_pct = "100".to_string();
-    let two_hundred_pct = "200".to_string();
+    let dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[txt_record("_dmarc.example.com", "p=reject; v=DMARC1")],
+    );
+    assert!(!dmarc.has_record());
+    assert_eq!(dmarc.returned_record, NO.to_string());
+    assert_eq!(
+        dmarc.ignored_records,
+        Some("\"p=reject; v=DMARC1\"".to_string())
+    );
+}
 
-    dmarc.pct = Some(not_number.clone());
+#[test]
+fn dmarc_set_cname_chain() {
+    use trust_dns_client::rr::{Name, RData};
+
+    let cname_record = |name: &str, target: &str| {
+        Record::from_rdata(
+            Name::from_utf8(name).unwrap(),
+            300,
+            RData::CNAME(Name::from_utf8(target).unwrap()),
+        )
+    };
+
+    let answers = [
+        cname_record("_dmarc.example.com", "example.com.dmarc.vendor.net"),
+        txt_record("example.com.dmarc.vendor.net", "v=DMARC1; p=quarantine"),
+    ];
+    let mut cname_chain = CnameChain::new("_dmarc.example.com");
+    cname_chain.follow(&answers);
+
+    let mut dmarc = Dmarc::from_answers("example.com", "example.com", &answers);
+    dmarc.set_cname_chain(&cname_chain);
+    assert_eq!(dmarc.record_type, TXT_RECORD.to_string());
+    assert_eq!(dmarc.p, Some(TagAction::Qurantine));
     assert_eq!(
-        DmarcFieldResult::InvalidConfig(format!("{} <- Is not a number", not_number)),
-        dmarc.check_pct()
+        dmarc.cname_chain,
+        Some("_dmarc.example.com -> example.com.dmarc.vendor.net".to_string())
+    );
+    assert_eq!(
+        dmarc.config_cname_chain,
+        Some(DmarcFieldResult::ValidConfig)
     );
 
-    dmarc.pct = Some(zero_pct.clone());
+    let answers = [
+        cname_record("_dmarc.example.com", "a.vendor.net"),
+        cname_record("a.vendor.net", "_dmarc.example.com"),
+    ];
+    let mut cname_chain = CnameChain::new("_dmarc.example.com");
+    cname_chain.follow(&answers);
+
+    let mut dmarc = Dmarc::from_answers("example.com", "example.com", &answers);
+    dmarc.set_cname_chain(&cname_chain);
+    assert_eq!(dmarc.record_type, CNAME_RECORD.to_string());
+    assert_eq!(dmarc.record_count, 1);
     assert_eq!(
-        DmarcFieldResult::InvalidConfig(ERR_P_FLAG_MISSING.to_string()),
-        dmarc.check_pct()
+        dmarc.config_cname_chain,
+        Some(DmarcFieldResult::InvalidConfig(format!(
+            "{} _dmarc.example.com",
+            ERR_CNAME_LOOP
+        )))
     );
 
-    dmarc.p = Some(TagAction::None);
+    // A subdomain CNAMEd to a retired vendor has no record, so the fallback still runs
+    let answers = [cname_record(
+        "_dmarc.sub.example.com",
+        "sub.dmarc.vendor.net",
+    )];
+    let mut cname_chain = CnameChain::new("_dmarc.sub.example.com");
+    cname_chain.follow(&answers);
+
+    let mut dmarc = Dmarc::from_answers("sub.example.com", "sub.example.com", &answers);
+    dmarc.set_cname_chain(&cname_chain);
+    assert!(!dmarc.has_record());
     assert_eq!(
-        DmarcFieldResult::VeryBadConfig(ERR_POLICY_IS_NONE.to_string()),
-        dmarc.check_pct()
+        dmarc.config_cname_chain,
+        Some(DmarcFieldResult::InvalidConfig(format!(
+            "{} sub.dmarc.vendor.net",
+            ERR_CNAME_NO_RECORD
+        )))
     );
 
-    dmarc.p = Some(TagAction::Qurantine);
-    dmarc.pct = Some(zero_pct.clone());
+    // ...and the dead end is still reported on the organizational domain's record
+    let mut org_dmarc = Dmarc::from_answers(
+        "sub.example.com",
+        "example.com",
+        &[txt_record("_dmarc.example.com", "v=DMARC1; p=reject")],
+    );
+    org_dmarc.keep_cname_chain(&dmarc);
+    assert!(org_dmarc.has_record());
+    assert_eq!(org_dmarc.config_cname_chain, dmarc.config_cname_chain);
+}
+
+#[test]
+fn dmarc_from_answers_txt_strings() {
+    use trust_dns_client::rr::{Name, RData};
+
+    let txt_strings_record = |strings: Vec<String>| {
+        Record::from_rdata(
+            Name::from_utf8("_dmarc.example.com").unwrap(),
+            300,
+            RData::TXT(TXT::new(strings)),
+        )
+    };
+
+    let answers = [txt_strings_record(vec![
+        "v=DMARC1; p=reject; ".to_string(),
+        "rua=mailto:dmarc@example.com".to_string(),
+    ])];
+    let dmarc = Dmarc::from_answers("example.com", "example.com", &answers);
     assert_eq!(
-        DmarcFieldResult::VeryBadConfig(zero_pct.clone()),
-        dmarc.check_pct()
+        dmarc.raw_data,
+        "\"v=DMARC1; p=reject; rua=mailto:dmarc@example.com\""
+    );
+    assert_eq!(dmarc.txt_string_lengths, Some("20 28".to_string()));
+    assert_eq!(
+        dmarc.config_txt_strings,
+        Some(DmarcFieldResult::ValidConfig)
     );
 
-    dmarc.pct = Some(twenty_six_pct.clone());
+    let answers = [txt_strings_record(vec![
+        "v=DMARC1; p=reject; rua=mailto:dm".to_string(),
+        "arc@example.com".to_string(),
+    ])];
+    let dmarc = Dmarc::from_answers("example.com", "example.com", &answers);
+    assert_eq!(dmarc.rua_addresses, Some("dmarc@example.com".to_string()));
     assert_eq!(
-        DmarcFieldResult::BadConfig(twenty_six_pct.clone()),
-        dmarc.check_pct()
+        dmarc.config_txt_strings,
+        Some(DmarcFieldResult::BadConfig(format!(
+            "{} `rua` at 33",
+            ERR_STRING_SPLIT_IN_TAG
+        )))
     );
 
-    dmarc.pct = Some(one_hundred_pct.clone());
-    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_pct());
+    let rua = (0..20)
+        .map(|i| format!("mailto:dmarc-reports-{}@example.com", i))
+        .collect::<Vec<_>>()
+        .join(",");
+    let answers = [txt_strings_record(vec![
+        "v=DMARC1; p=none;".to_string(),
+        format!("rua={};", &rua[..250]),
+        format!("ruf={};", &rua[250..500]),
+    ])];
+    let dmarc = Dmarc::from_answers("example.com", "example.com", &answers);
+    assert_eq!(
+        dmarc.config_txt_strings,
+        Some(DmarcFieldResult::BadConfig(format!(
+            "578 {}",
+            ERR_UDP_TRUNCATED
+        )))
+    );
+}
+
+#[test]
+fn dmarc_duplicate_tags() {
+    let dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[txt_record(
+            "_dmarc.example.com",
+            "v=DMARC1; p=reject; rua=mailto:a@example.com; p=none; rua=mailto:b@example.com",
+        )],
+    );
 
-    dmarc.pct = Some(two_hundred_pct.clone());
+    assert_eq!(dmarc.p, Some(TagAction::Reject));
+    assert_eq!(dmarc.others, None);
     assert_eq!(
-        DmarcFieldResult::InvalidConfig(two_hundred_pct.clone()),
-        dmarc.check_pct()
+        dmarc.config_duplicate_tags,
+        Some(DmarcFieldResult::InvalidConfig(format!("{0} `p`: `reject` and `none`, {0} `rua`: `mailto:a@example.com` and `mailto:b@example.com`",
+            ERR_DUPLICATE_TAG
+        )))
     );
+    assert_eq!(dmarc.policy_domain, Some("example.com".to_string()));
+    assert_eq!(dmarc.applied_policy, None);
+    assert_eq!(dmarc.applied_via, None);
+}
 
-    dmarc.p = Some(TagAction::Reject);
-    dmarc.pct = Some(two_hundred_pct.clone());
+#[test]
+fn dmarc_suggestions() {
+    let dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[txt_record(
+            "_dmarc.example.com",
+            "v=DMARC1; p=quarentine; sp=rejct; ruo=mailto:dmarc@example.com; rf=afr; x=1",
+        )],
+    );
     assert_eq!(
-        DmarcFieldResult::InvalidConfig(two_hundred_pct.clone()),
-        dmarc.check_pct()
+        dmarc.suggestions,
+        Some(
+            "`ruo=`: did you mean `rua`?, `p=quarentine`: did you mean `p=quarantine`?, \
+             `sp=rejct`: did you mean `sp=reject`?, `rf=afr`: did you mean `rf=afrf`?"
+                .to_string()
+        )
     );
 
-    dmarc.pct = Some(twenty_six_pct.clone());
-    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_pct());
+    let dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[txt_record("_dmarc.example.com", "v=DMARC 1; p=reject")],
+    );
+    assert_eq!(dmarc.returned_record, NO.to_string());
+    assert_eq!(
+        dmarc.suggestions,
+        Some("`v=DMARC 1`: did you mean `v=DMARC1`?".to_string())
+    );
+
+    let dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[txt_record("_dmarc.example.com", "v=spf1 -all")],
+    );
+    assert_eq!(dmarc.suggestions, None);
 }
 
 #[test]
-fn dmarc_check_sp() {
-    let mut dmarc = Dmarc::default();
+fn dmarc_set_score() {
+    let weights = ScoreWeights::default();
+
+    let mut dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[txt_record(
+            "_dmarc.example.com",
+            "v=DMARC1; p=reject; rua=mailto:dmarc@example.com",
+        )],
+    );
+    dmarc.set_score(&weights);
+    assert_eq!(dmarc.score, Some(100));
+    assert_eq!(dmarc.grade, Some(Grade::A));
+
+    let mut dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[txt_record("_dmarc.example.com", "v=DMARC1; p=none")],
+    );
+    dmarc.set_score(&weights);
+    assert!(dmarc.score < Some(100));
+
+    let mut dmarc = Dmarc::from_answers("example.com", "example.com", &[]);
+    dmarc.set_score(&weights);
+    assert_eq!(dmarc.score, Some(0));
+    assert_eq!(dmarc.grade, Some(Grade::F));
+
+    // Receivers that do not recover the missing `;` see no policy
+    let mut dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[txt_record("_dmarc.example.com", "v=DMARC1 p=reject")],
+    );
+    dmarc.set_score(&weights);
+    assert_eq!(dmarc.applied_policy, None);
+    assert_eq!(
+        dmarc.config_syntax,
+        Some(DmarcFieldResult::InvalidConfig(
+            "Missing `;` before `p` at 9..10".to_string()
+        ))
+    );
+    assert_eq!(dmarc.score, Some(0));
+    assert_eq!(dmarc.grade, Some(Grade::F));
+}
 
-    dmarc.p = None;
-    dmarc.sp = None;
+#[test]
+fn dmarc_set_spf() {
+    let mut dmarc = Dmarc::from_answers("example.com", "example.com", &[]);
+    dmarc.set_spf(&SpfCheck::from_answers(&[
+        txt_record("example.com", "google-site-verification=abc"),
+        txt_record("example.com", "v=spf1 include:_spf.example.net ~all"),
+    ]));
     assert_eq!(
-        DmarcFieldResult::InvalidConfig(ERR_P_FLAG_MISSING_AND_SP_NOT_SET.to_string()),
-        dmarc.check_sp()
+        dmarc.spf_record,
+        Some("v=spf1 include:_spf.example.net ~all".to_string())
+    );
+    assert_eq!(
+        dmarc.spf_directives,
+        Some("include:_spf.example.net ~all".to_string())
+    );
+    assert_eq!(dmarc.spf_all, Some("~all".to_string()));
+    assert_eq!(
+        dmarc.config_spf,
+        Some(DmarcFieldResult::BadConfig(
+            "~all: Other hosts only soft fail".to_string()
+        ))
     );
 
-    dmarc.sp = Some(TagAction::None);
+    dmarc.set_spf(&SpfCheck::from_answers(&[
+        txt_record("example.com", "v=spf1 -all"),
+        txt_record("example.com", "v=spf1 +all"),
+    ]));
     assert_eq!(
-        DmarcFieldResult::InvalidConfig(ERR_P_FLAG_MISSING.to_string()),
-        dmarc.check_sp()
+        dmarc.config_spf,
+        Some(DmarcFieldResult::InvalidConfig(
+            "2 SPF records: permerror".to_string()
+        ))
     );
+}
 
-    dmarc.p = Some(TagAction::None);
+#[test]
+fn dmarc_set_spoofability() {
+    let mut dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[txt_record(
+            "_dmarc.example.com",
+            "v=DMARC1; p=reject; sp=none; pct=100",
+        )],
+    );
+    dmarc.set_spoofability(&SpfCheck::from_txt("v=spf1 -all"), None, true);
+    assert_eq!(dmarc.spoofability, Some("Subdomains spoofable".to_string()));
     assert_eq!(
-        DmarcFieldResult::BadConfig(ERR_IGNORE_SUBDOMAIN_DMARC_FAILS.to_string()),
-        dmarc.check_sp()
+        dmarc.spoofability_reasons,
+        Some("sp=none: Subdomains can be spoofed".to_string())
     );
 
-    dmarc.p = Some(TagAction::Reject);
+    let mut dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[txt_record("_dmarc.example.com", "v=DMARC1; p=none")],
+    );
+    dmarc.set_spoofability(&SpfCheck::from_txt("v=spf1 ip4:192.0.2.1 +all"), None, true);
+    assert_eq!(dmarc.spoofability, Some("Directly spoofable".to_string()));
     assert_eq!(
-        DmarcFieldResult::VeryBadConfig(ERR_PERMITS_SUBDOMAIN_SPOOFING.to_string()),
-        dmarc.check_sp()
+        dmarc.spoofability_reasons,
+        Some(
+            "DMARC policy is `none`, SPF passes or is neutral for any host with `+all`".to_string()
+        )
     );
 
-    dmarc.p = Some(TagAction::Qurantine);
+    let redirect = SpfCheck::from_txt("v=spf1 redirect=_spf.example.net");
+    dmarc.set_spoofability(&redirect, Some(Qualifier::Fail), true);
     assert_eq!(
-        DmarcFieldResult::BadConfig(ERR_IGNORE_SUBDOMAIN_DMARC_FAILS.to_string()),
-        dmarc.check_sp()
+        dmarc.spoofability,
+        Some("Spoofable to lenient receivers".to_string())
     );
+    dmarc.set_spoofability(&redirect, None, true);
+    assert_eq!(dmarc.spoofability, Some("Unknown".to_string()));
+}
 
-    dmarc.sp = Some(TagAction::Reject);
-    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_sp());
+#[test]
+fn dmarc_set_dkim_keys() {
+    let mut dmarc = Dmarc::new("example.com", None);
+    dmarc.set_dkim_keys(&[]);
+    assert_eq!(dmarc.config_dkim, None);
+
+    dmarc.set_dkim_keys(&[
+        DkimKeyCheck::from_txt("old", "v=DKIM1; p="),
+        DkimKeyCheck::from_answers("s1", &[]),
+    ]);
+    assert_eq!(dmarc.dkim_keys, Some("old: revoked, s1: none".to_string()));
+    assert_eq!(
+        dmarc.config_dkim,
+        Some(DmarcFieldResult::InvalidConfig(
+            "old: Empty p: Key revoked, s1: No key record".to_string()
+        ))
+    );
+}
 
-    dmarc.sp = Some(TagAction::Qurantine);
-    assert_eq!(DmarcFieldResult::ValidConfig, dmarc.check_sp());
+#[test]
+fn dmarc_set_mx() {
+    use trust_dns_client::rr::{rdata::MX, Name, RData};
+
+    let null_mx = MxCheck::from_answers(&[Record::from_rdata(
+        Name::from_utf8("example.com").unwrap(),
+        300,
+        RData::MX(MX::new(0, Name::root())),
+    )]);
+
+    let mut dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[txt_record("_dmarc.example.com", "v=DMARC1; p=reject")],
+    );
+    dmarc.set_mx(&null_mx, &SpfCheck::from_txt("v=spf1 -all"));
+    assert_eq!(dmarc.mx_records, Some("0 .".to_string()));
+    assert_eq!(dmarc.config_mx, Some(DmarcFieldResult::ValidConfig));
+    assert_eq!(dmarc.mail_class, Some("Parked and locked".to_string()));
+
+    dmarc.set_mx(&null_mx, &SpfCheck::from_txt("v=spf1 mx -all"));
+    assert_eq!(dmarc.mail_class, Some("Parked but spoofable".to_string()));
+
+    dmarc.set_mx(&MxCheck::from_answers(&[]), &SpfCheck::default());
+    assert_eq!(dmarc.mail_class, Some("Parked but spoofable".to_string()));
+    assert!(
+        matches!(dmarc.config_mx, Some(DmarcFieldResult::BadConfig(finding)) if finding.starts_with("No MX"))
+    );
 }
 
 #[test]
-fn dmarc_check_v_and_p_order() {
-    let mut dmarc_entries: Vec<DmarcEntry> = Vec::new();
+fn dmarc_check_parked() {
+    let mut dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[txt_record(
+            "_dmarc.example.com",
+            "v=DMARC1; p=reject; sp=none",
+        )],
+    );
+    dmarc.check_parked(&MxCheck::default(), &SpfCheck::from_txt("v=spf1 -all"));
+    assert_eq!(
+        dmarc.parked_missing_records,
+        Some(
+            "_dmarc.example.com TXT \"v=DMARC1; p=reject; sp=reject\" | example.com MX 0 ."
+                .to_string()
+        )
+    );
+    assert_eq!(
+        dmarc.config_parked,
+        Some(DmarcFieldResult::VeryBadConfig(
+            "Parked domain missing DMARC p=reject and sp=reject, \
+             Null MX replacing any other MX"
+                .to_string()
+        ))
+    );
+
+    let mut dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[txt_record("_dmarc.example.com", "v=DMARC1; p=reject")],
+    );
+    let null_mx = MxCheck {
+        exchanges: vec![(0, ".".to_string())],
+        null_mx: true,
+        implicit_mx: false,
+        findings: vec![],
+    };
+    dmarc.check_parked(&null_mx, &SpfCheck::from_txt("v=spf1 -all"));
+    assert_eq!(dmarc.parked_missing_records, Some("".to_string()));
+    assert_eq!(dmarc.config_parked, Some(DmarcFieldResult::ValidConfig));
+}
+
+#[test]
+fn dmarc_set_mta_sts() {
+    let mx_check = MxCheck {
+        exchanges: vec![(10, "mx1.example.com.".to_string())],
+        null_mx: false,
+        implicit_mx: false,
+        findings: Vec::new(),
+    };
+
+    let mut mta_sts_check = MtaStsCheck::from_txt("v=STSv1; id=20230601");
+    mta_sts_check.check_policy(
+        Ok("version: STSv1\nmode: enforce\nmx: *.example.com\nmax_age: 604800\n".to_string()),
+        &mx_check,
+    );
+
+    let mut dmarc = Dmarc::new("example.com", None);
+    dmarc.set_mta_sts(&mta_sts_check);
+    assert_eq!(dmarc.mta_sts_id, Some("20230601".to_string()));
+    assert_eq!(dmarc.mta_sts_mode, Some("enforce".to_string()));
+    assert_eq!(dmarc.mta_sts_mx, Some("*.example.com".to_string()));
+    assert_eq!(dmarc.mta_sts_max_age, Some(604800));
+    assert_eq!(dmarc.config_mta_sts, Some(DmarcFieldResult::ValidConfig));
 
+    dmarc.set_mta_sts(&MtaStsCheck::from_answers(&[]));
     assert_eq!(
-        Dmarc::check_v_and_p_order(&dmarc_entries),
-        DmarcFieldResult::InvalidConfig(ERR_MISSING_V_OR_P_FLAG.to_string()),
+        dmarc.config_mta_sts,
+        Some(DmarcFieldResult::BadConfig(
+            "No MTA-STS record: Senders may deliver without TLS".to_string()
+        ))
     );
+}
 
-    dmarc_entries.push(DmarcEntry::new("A", DMARC1));
+#[test]
+fn dmarc_set_tls_rpt() {
+    let mut dmarc = Dmarc::new("example.com", None);
+    dmarc.set_tls_rpt(&TlsRptCheck::from_txt(
+        "v=TLSRPTv1; rua=mailto:tlsrpt@example.com,https://reports.example.com/tlsrpt",
+    ));
     assert_eq!(
-        Dmarc::check_v_and_p_order(&dmarc_entries),
-        DmarcFieldResult::InvalidConfig(ERR_MISSING_V_OR_P_FLAG.to_string()),
+        dmarc.tls_rpt_rua,
+        Some("mailto:tlsrpt@example.com https://reports.example.com/tlsrpt".to_string())
     );
+    assert_eq!(dmarc.config_tls_rpt, Some(DmarcFieldResult::ValidConfig));
 
-    dmarc_entries.push(DmarcEntry::new("B", TAG_NONE));
+    dmarc.set_tls_rpt(&TlsRptCheck::from_txt("v=TLSRPTv1; rua=tlsrpt@example.com"));
+    assert_eq!(dmarc.tls_rpt_rua, Some("".to_string()));
     assert_eq!(
-        Dmarc::check_v_and_p_order(&dmarc_entries),
-        DmarcFieldResult::InvalidConfig(ERR_FIRST_FLAG_NOT_V.to_string()),
+        dmarc.config_tls_rpt,
+        Some(DmarcFieldResult::InvalidConfig(
+            "Missing URI scheme `tlsrpt@example.com`".to_string()
+        ))
     );
+}
 
-    dmarc_entries[0] = DmarcEntry::new(V_TAG, DMARC1);
+#[test]
+fn dmarc_set_bimi() {
+    let bimi_record = "v=BIMI1; l=https://example.com/logo.svg; a=https://example.com/vmc.pem";
+
+    let mut dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[txt_record("_dmarc.example.com", "v=DMARC1; p=reject")],
+    );
+    dmarc.set_bimi(BimiCheck::from_txt(bimi_record));
     assert_eq!(
-        Dmarc::check_v_and_p_order(&dmarc_entries),
-        DmarcFieldResult::InvalidConfig(ERR_SECOND_FLAG_NOT_P.to_string()),
+        dmarc.bimi_logo,
+        Some("https://example.com/logo.svg".to_string())
+    );
+    assert_eq!(dmarc.config_bimi, Some(DmarcFieldResult::ValidConfig));
+
+    let mut dmarc = Dmarc::from_answers(
+        "example.com",
+        "example.com",
+        &[txt_record(
+            "_dmarc.example.com",
+            "v=DMARC1; p=quarantine; pct=20; sp=none",
+        )],
+    );
+    dmarc.set_bimi(BimiCheck::from_txt(bimi_record));
+    assert_eq!(
+        dmarc.config_bimi,
+        Some(DmarcFieldResult::InvalidConfig("DMARC pct=20: Logos need the policy applied to all mail, sp=none: Logos need subdomains enforced too".to_string()))
     );
 
-    dmarc_entries[1] = DmarcEntry::new(P_TAG, TAG_NONE);
+    // A missing record stays informational whatever the DMARC policy
+    dmarc.set_bimi(BimiCheck::from_answers(&[]));
     assert_eq!(
-        Dmarc::check_v_and_p_order(&dmarc_entries),
-        DmarcFieldResult::ValidConfig,
+        dmarc.config_bimi,
+        Some(DmarcFieldResult::BadConfig("No BIMI record".to_string()))
     );
 }
 
@@ -742,9 +2954,10 @@ fn tag_action_to_enum() {
 }
 
 #[test]
+#[allow(clippy::field_reassign_with_default)]
 fn dmarc_new() {
     let test_domain = "google.com";
-    let valid = "Valid";
+    let valid = DmarcFieldResult::ValidConfig;
 
     let dmarc = Dmarc::new(test_domain, None);
     let mut dmarc_compare = Dmarc::default();
@@ -753,6 +2966,9 @@ fn dmarc_new() {
     dmarc_compare.returned_record = NO.to_string();
     assert_eq!(dmarc, dmarc_compare);
 
+    dmarc_compare.record_count = 1;
+    dmarc_compare.config_record_count = Some(valid.clone());
+
     let raw_cname = "microsoft.com".to_string();
     let cname_record = DmarcRecordType::Cname(Some(raw_cname.clone()));
     let dmarc = Dmarc::new(test_domain, Some(cname_record));
@@ -789,11 +3005,22 @@ fn dmarc_new() {
     dmarc_compare.record_type = TXT_RECORD.to_string();
     dmarc_compare.v = Some(DmarcVersion::Dmarc1);
     dmarc_compare.p = Some(TagAction::None);
-    dmarc_compare.config_v_p_order = Some(valid.to_string());
-    dmarc_compare.config_v = Some(dmarc.check_v().to_string());
-    dmarc_compare.config_p = Some(dmarc.check_p().to_string());
-    dmarc_compare.config_pct = Some(dmarc.check_pct().to_string());
-    dmarc_compare.config_sp = Some(dmarc.check_sp().to_string());
+    dmarc_compare.config_syntax = Some(valid.clone());
+    dmarc_compare.config_v_p_order = Some(valid.clone());
+    dmarc_compare.config_v = Some(dmarc.check_v());
+    dmarc_compare.config_p = Some(dmarc.check_p());
+    dmarc_compare.config_pct = Some(dmarc.check_pct());
+    dmarc_compare.config_sp = Some(dmarc.check_sp());
+    dmarc_compare.config_rua = Some(valid.clone());
+    dmarc_compare.config_ruf = Some(valid.clone());
+    dmarc_compare.config_adkim = Some(valid.clone());
+    dmarc_compare.config_aspf = Some(valid.clone());
+    dmarc_compare.config_fo = Some(valid.clone());
+    dmarc_compare.config_rf = Some(valid.clone());
+    dmarc_compare.config_ri = Some(valid.clone());
+    dmarc_compare.config_np = Some(valid.clone());
+    dmarc_compare.config_psd = Some(valid.clone());
+    dmarc_compare.config_t = Some(valid.clone());
     dmarc_compare.raw_data = format!("\"{}\"", raw_txt);
     assert_eq!(dmarc, dmarc_compare);
 }
@@ -0,0 +1,125 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use std::collections::HashSet;
+
+/// Copy of https://publicsuffix.org/list/public_suffix_list.dat shipped with the binary.
+const BUNDLED_PUBLIC_SUFFIX_LIST: &str = include_str!("../data/public_suffix_list.dat");
+
+const COMMENT: &str = "//";
+const WILDCARD: &str = "*.";
+const EXCEPTION: &str = "!";
+
+#[derive(Debug, Default)]
+pub struct PublicSuffixList {
+    rules: HashSet<String>,
+    wildcards: HashSet<String>,
+    exceptions: HashSet<String>,
+}
+
+impl PublicSuffixList {
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED_PUBLIC_SUFFIX_LIST)
+    }
+
+    pub fn parse(list: &str) -> Self {
+        let mut psl = Self::default();
+
+        for line in list.lines() {
+            let rule = match line.split_whitespace().next() {
+                Some(rule) if !rule.starts_with(COMMENT) => rule.to_lowercase(),
+                _ => continue,
+            };
+
+            if let Some(r) = rule.strip_prefix(WILDCARD) {
+                psl.wildcards.insert(r.to_string());
+            } else if let Some(r) = rule.strip_prefix(EXCEPTION) {
+                psl.exceptions.insert(r.to_string());
+            } else {
+                psl.rules.insert(rule);
+            }
+        }
+
+        psl
+    }
+
+    /// Returns the Organizational Domain, the public suffix plus one label (RFC 7489 §3.2).
+    pub fn organizational_domain(&self, domain_name: &str) -> Option<String> {
+        let domain_name = domain_name.trim_end_matches('.').to_lowercase();
+        let labels: Vec<&str> = domain_name.split('.').collect();
+
+        if labels.iter().any(|l| l.is_empty()) {
+            return None;
+        }
+
+        let suffix_len = self.public_suffix_len(&labels);
+
+        match labels.len() > suffix_len {
+            true => Some(labels[labels.len() - suffix_len - 1..].join(".")),
+            false => None,
+        }
+    }
+
+    /// Number of labels in the longest matching rule, an unlisted TLD is its own suffix.
+    fn public_suffix_len(&self, labels: &[&str]) -> usize {
+        for i in 0..labels.len() {
+            let candidate = labels[i..].join(".");
+
+            if self.exceptions.contains(&candidate) {
+                return labels.len() - i - 1;
+            }
+
+            if self.rules.contains(&candidate)
+                || (i + 1 < labels.len() && self.wildcards.contains(&labels[i + 1..].join(".")))
+            {
+                return labels.len() - i;
+            }
+        }
+
+        1
+    }
+}
+
+// Tests
+#[test]
+fn public_suffix_list_organizational_domain() {
+    let psl = PublicSuffixList::parse(
+        "// comment\ncom\nuk\nco.uk\n\n*.ck\n!www.ck\n// ===END ICANN DOMAINS===",
+    );
+
+    assert_eq!(
+        psl.organizational_domain("mail.example.co.uk"),
+        Some("example.co.uk".to_string())
+    );
+    assert_eq!(
+        psl.organizational_domain("Example.COM."),
+        Some("example.com".to_string())
+    );
+    assert_eq!(
+        psl.organizational_domain("a.b.example.ck"),
+        Some("b.example.ck".to_string())
+    );
+    assert_eq!(
+        psl.organizational_domain("www.ck"),
+        Some("www.ck".to_string())
+    );
+    assert_eq!(
+        psl.organizational_domain("sub.example.test"),
+        Some("example.test".to_string())
+    );
+    assert_eq!(psl.organizational_domain("co.uk"), None);
+    assert_eq!(psl.organizational_domain("example..com"), None);
+}
+
+#[test]
+fn public_suffix_list_bundled() {
+    let psl = PublicSuffixList::bundled();
+
+    assert_eq!(
+        psl.organizational_domain("mail.dwp.gov.uk"),
+        Some("dwp.gov.uk".to_string())
+    );
+    assert_eq!(
+        psl.organizational_domain("www.cia.gov"),
+        Some("cia.gov".to_string())
+    );
+}
@@ -1,15 +1,31 @@
+use crate::bimi::{self, BimiCheck};
+use crate::cname::{ChainStep, CnameChain};
+use crate::discovery::{self, TreeWalk};
+use crate::dkim::{self, SelectorDiscovery, SelectorList};
+use crate::dkim_key::DkimKeyCheck;
+use crate::mta_sts::{self, MtaStsCheck, PolicyFetcher};
+use crate::mx::MxCheck;
+use crate::parse::{Dmarc, ReportAuthorization};
+use crate::psl::PublicSuffixList;
+use crate::resolver::{ResolveError, Resolver};
+use crate::score::ScoreWeights;
+use crate::spf::{Qualifier, SpfCheck, SpfRecord};
+use crate::spf_expansion::{SpfExpansion, MAX_DNS_LOOKUPS};
+use crate::tls_rpt::{self, TlsRptCheck};
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 use std::{
     net::{IpAddr, Ipv4Addr, SocketAddr},
     str::FromStr,
+    sync::Arc,
 };
 use tokio::sync::mpsc;
 use trust_dns_client::client::{Client, SyncClient};
+use trust_dns_client::error::ClientError;
 use trust_dns_client::rr::{DNSClass, Name, Record, RecordType};
 use trust_dns_client::udp::UdpClientConnection;
 
-pub const DNS_SERVERS: &'static [&'static str] = &[
+pub const DNS_SERVERS: &[&str] = &[
     "8.8.8.8",
     "9.9.9.9",
     "8.8.4.4",
@@ -60,38 +76,296 @@ pub const DNS_SERVERS: &'static [&'static str] = &[
     "94.140.15.15",
 ];
 
-pub async fn try_query(domain_name: String, tx: mpsc::Sender<(String, Vec<Record>)>) {
-    // Read URIs to query
-    let name = Name::from_utf8(format!("_dmarc.{}", domain_name)).unwrap();
+/// Settings shared by every query task.
+pub struct QueryOptions {
+    pub public_suffix_list: PublicSuffixList,
+    pub tree_walk: bool,
+    pub dmarcbis: bool,
+    pub parked: bool,
+    pub spf_expansion: bool,
+    pub score_weights: ScoreWeights,
+    pub dkim_selectors: Option<SelectorList>,
+    pub mta_sts_fetcher: Option<PolicyFetcher>,
+    pub tls_rpt: bool,
+    pub bimi: bool,
+}
 
-    loop {
-        // Clone for request
-        let name = name.clone();
+pub async fn try_query(
+    domain_name: String,
+    dkim_selectors: Vec<String>,
+    options: Arc<QueryOptions>,
+    tx: mpsc::Sender<(String, Dmarc)>,
+) {
+    let organizational_domain = options
+        .public_suffix_list
+        .organizational_domain(&domain_name);
+
+    let (cname_chain, dmarc_answers) =
+        lookup_txt_following_cnames(&format!("_dmarc.{}", domain_name)).await;
+
+    let mut dmarc = Dmarc::from_answers(&domain_name, &domain_name, &dmarc_answers);
+    dmarc.set_cname_chain(&cname_chain);
+
+    // Answers already fetched for `_dmarc` names, reused by the tree walk
+    let mut dmarc_answers_by_name = vec![(
+        domain_name.trim_end_matches('.').to_lowercase(),
+        dmarc_answers,
+    )];
+
+    // Without a record of its own a subdomain falls back to its organizational domain
+    if !dmarc.has_record() {
+        if let Some(org_domain) = organizational_domain
+            .as_ref()
+            .filter(|org_domain| **org_domain != domain_name.to_lowercase())
+        {
+            let (org_cname_chain, org_answers) =
+                lookup_txt_following_cnames(&format!("_dmarc.{}", org_domain)).await;
+            let mut org_dmarc = Dmarc::from_answers(&domain_name, org_domain, &org_answers);
+            org_dmarc.set_cname_chain(&org_cname_chain);
+            dmarc_answers_by_name.push((org_domain.clone(), org_answers));
+
+            if org_dmarc.has_record() {
+                org_dmarc.keep_cname_chain(&dmarc);
+                dmarc = org_dmarc;
+            }
+        }
+    }
 
-        // Open Udp connection for DNS client
-        let conn = UdpClientConnection::with_timeout(
-            get_dns_ip().await,
-            std::time::Duration::from_millis(200),
+    let mut report_authorizations: Vec<ReportAuthorization> = Vec::new();
+
+    for target_domain in dmarc.external_report_domains(&options.public_suffix_list) {
+        let auth_answers = lookup(
+            &format!("{}._report._dmarc.{}", dmarc.policy_domain(), target_domain),
+            RecordType::TXT,
         )
-        .unwrap();
+        .await;
+
+        report_authorizations.push(ReportAuthorization::new(&target_domain, &auth_answers));
+    }
 
-        // DNS client stuff
-        let client = SyncClient::new(conn);
+    dmarc.set_report_authorizations(&report_authorizations, &options.public_suffix_list);
+    dmarc.set_organizational_domain(organizational_domain.clone());
 
-        // Spawn blocking task and check for errors
-        match tokio::task::spawn_blocking(move || {
-            client.query(&name, DNSClass::IN, RecordType::TXT)
+    let spf_answers = lookup(&domain_name, RecordType::TXT).await;
+    let spf_check = SpfCheck::from_answers(&spf_answers);
+    dmarc.set_spf(&spf_check);
+
+    let mut mx_check = MxCheck::from_answers(&lookup(&domain_name, RecordType::MX).await);
+    if mx_check.exchanges.is_empty() {
+        let mut addresses = lookup(&domain_name, RecordType::A).await;
+        addresses.extend(lookup(&domain_name, RecordType::AAAA).await);
+        mx_check.set_addresses(&addresses);
+    }
+    dmarc.set_mx(&mx_check, &spf_check);
+    let redirect_all = match &spf_check.record {
+        Some(spf_record) => redirect_all(spf_record).await,
+        None => None,
+    };
+    dmarc.set_spoofability(&spf_check, redirect_all, mx_check.receives_mail());
+
+    // Expansion decides each query from the last answer, so it runs on a blocking resolver
+    if let Some(spf_record) = spf_check.record.clone().filter(|_| options.spf_expansion) {
+        let spf_domain = domain_name.clone();
+        let spf_expansion = tokio::task::spawn_blocking(move || {
+            SpfExpansion::expand(&DnsResolver, &spf_domain, &spf_record)
         })
         .await
-        .unwrap()
+        .map_err(|e| print_err!("Failed to expand SPF record for {} - {}", domain_name, e));
+
+        if let Ok(spf_expansion) = spf_expansion {
+            dmarc.set_spf_expansion(&spf_expansion);
+        }
+    }
+
+    if !dkim_selectors.is_empty() || options.dkim_selectors.is_some() {
+        let (discovery, key_checks) = check_dkim(
+            &domain_name,
+            &dkim_selectors,
+            options.dkim_selectors.as_ref(),
+        )
+        .await;
+
+        if let Some(discovery) = discovery {
+            dmarc.set_dkim_selectors(&discovery);
+        }
+        dmarc.set_dkim_keys(&key_checks);
+    }
+
+    if let Some(fetcher) = &options.mta_sts_fetcher {
+        let (_, mta_sts_answers) =
+            lookup_txt_following_cnames(&format!("{}.{}", mta_sts::MTA_STS_LABEL, domain_name))
+                .await;
+        let mut mta_sts_check = MtaStsCheck::from_answers(&mta_sts_answers);
+
+        // The policy is only fetched once a record announces it
+        if mta_sts_check.has_record() {
+            mta_sts_check.check_policy(fetcher.fetch(&domain_name).await, &mx_check);
+        }
+
+        dmarc.set_mta_sts(&mta_sts_check);
+    }
+
+    if options.tls_rpt {
+        let (_, tls_rpt_answers) =
+            lookup_txt_following_cnames(&format!("{}.{}", tls_rpt::TLS_RPT_LABEL, domain_name))
+                .await;
+        dmarc.set_tls_rpt(&TlsRptCheck::from_answers(&tls_rpt_answers));
+    }
+
+    if options.bimi {
+        let (_, bimi_answers) = lookup_txt_following_cnames(&bimi::record_name(&domain_name)).await;
+        let mut bimi_check = BimiCheck::from_answers(&bimi_answers);
+
+        // Without a record of its own a subdomain uses the one of its organizational domain
+        if let Some(org_domain) = organizational_domain
+            .as_ref()
+            .filter(|org_domain| **org_domain != domain_name.to_lowercase())
+        {
+            if !bimi_check.has_record() {
+                let (_, org_bimi_answers) =
+                    lookup_txt_following_cnames(&bimi::record_name(org_domain)).await;
+                bimi_check = BimiCheck::from_answers(&org_bimi_answers);
+            }
+        }
+
+        dmarc.set_bimi(bimi_check);
+    }
+
+    if options.tree_walk {
+        dmarc.set_tree_walk(&tree_walk(&domain_name, &dmarc_answers_by_name).await);
+    }
+
+    if options.dmarcbis {
+        dmarc.check_dmarcbis();
+    }
+
+    if options.parked {
+        dmarc.check_parked(&mx_check, &spf_check);
+    }
+
+    dmarc.set_score(&options.score_weights);
+
+    // Success to send domain name and parsed record to channel
+    let _ = tx.send((domain_name, dmarc)).await;
+}
+
+/// Looks up the TXT records at `name`, following CNAME records to the published records.
+async fn lookup_txt_following_cnames(name: &str) -> (CnameChain, Vec<Record>) {
+    let mut cname_chain = CnameChain::new(name);
+    let mut answers = lookup(name, RecordType::TXT).await;
+
+    while let ChainStep::Query(target) = cname_chain.follow(&answers) {
+        answers.extend(lookup(&target, RecordType::TXT).await);
+    }
+
+    (cname_chain, answers)
+}
+
+/// Follows the `redirect` of an SPF record without `all` to the `all` that ends the chain,
+/// giving up after as many redirects as receivers allow lookups.
+async fn redirect_all(spf_record: &SpfRecord) -> Option<Qualifier> {
+    let mut target = spf_record
+        .redirect
+        .clone()
+        .filter(|_| spf_record.all().is_none());
+
+    for _ in 0..MAX_DNS_LOOKUPS {
+        let spf_check = SpfCheck::from_answers(&lookup(target.as_ref()?, RecordType::TXT).await);
+        let spf_record = spf_check.record.filter(|record| record.errors.is_empty())?;
+
+        if let Some(all) = spf_record.all() {
+            return Some(all);
+        }
+
+        target = spf_record.redirect;
+    }
+
+    None
+}
+
+/// Discovers the policy for `domain_name` with the DMARCbis DNS tree walk, following CNAME
+/// records like the other `_dmarc` lookups and reusing the answers in `fetched`.
+async fn tree_walk(domain_name: &str, fetched: &[(String, Vec<Record>)]) -> TreeWalk {
+    let mut tree_walk = TreeWalk::default();
+
+    for name in discovery::tree_walk_names(domain_name) {
+        let answers = match fetched
+            .iter()
+            .find(|(fetched_name, _)| *fetched_name == name)
         {
-            Ok(dns_response) => {
-                // Success to send domain name and DNS response to channel
-                let _ = tx
-                    .send((domain_name, dns_response.answers().to_owned()))
-                    .await;
-                return;
+            Some((_, answers)) => answers.clone(),
+            None => {
+                lookup_txt_following_cnames(&format!("_dmarc.{}", name))
+                    .await
+                    .1
+            }
+        };
+
+        if tree_walk.visit(&name, &answers) {
+            break;
+        }
+    }
+
+    tree_walk
+}
+
+/// Probes the given selectors, and those of the selector list when discovery is on, all at
+/// once. Keys are checked for the given selectors and for any the discovery found.
+async fn check_dkim(
+    domain_name: &str,
+    given: &[String],
+    selector_list: Option<&SelectorList>,
+) -> (Option<SelectorDiscovery>, Vec<DkimKeyCheck>) {
+    let mut probed = SelectorList::default();
+    probed.extend(given.iter().cloned());
+    if let Some(selector_list) = selector_list {
+        probed.extend(selector_list.selectors.iter().cloned());
+    }
+
+    let answers = futures::future::join_all(probed.selectors.iter().map(|selector| {
+        let name = dkim::key_name(selector, domain_name);
+        async move { lookup_txt_following_cnames(&name).await.1 }
+    }))
+    .await;
+
+    let mut discovery = selector_list.map(|_| SelectorDiscovery::default());
+    let mut key_checks: Vec<DkimKeyCheck> = Vec::new();
+
+    for (selector, answers) in probed.selectors.iter().zip(answers) {
+        let found = match (&mut discovery, selector_list) {
+            (Some(discovery), Some(selector_list))
+                if selector_list.selectors.contains(selector) =>
+            {
+                discovery.visit(selector, &answers)
             }
+            _ => false,
+        };
+
+        if found || given.contains(selector) {
+            key_checks.push(DkimKeyCheck::from_answers(selector, &answers));
+        }
+    }
+
+    (discovery, key_checks)
+}
+
+/// Queries random DNS servers until one responds, returning the answer section.
+pub async fn lookup(name: &str, record_type: RecordType) -> Vec<Record> {
+    let name = match Name::from_utf8(name) {
+        Ok(name) => name,
+        Err(_) => return Vec::new(),
+    };
+
+    loop {
+        // Clone for request
+        let name = name.clone();
+
+        // Spawn blocking task and check for errors
+        match tokio::task::spawn_blocking(move || query_dns(&name, record_type))
+            .await
+            .unwrap()
+        {
+            Ok(answers) => return answers,
             Err(_e) => {
                 //eprintln!("Failed '{}'", e)
             }
@@ -99,7 +373,40 @@ pub async fn try_query(domain_name: String, tx: mpsc::Sender<(String, Vec<Record
     }
 }
 
-async fn get_dns_ip() -> SocketAddr {
+/// Blocking resolver over the same DNS servers as `lookup`, giving up after a try per server.
+pub struct DnsResolver;
+
+impl Resolver for DnsResolver {
+    fn lookup(&self, name: &str, record_type: RecordType) -> Result<Vec<Record>, ResolveError> {
+        let dns_name =
+            Name::from_utf8(name).map_err(|_| ResolveError::InvalidName(name.to_string()))?;
+
+        for _ in 0..DNS_SERVERS.len() {
+            if let Ok(answers) = query_dns(&dns_name, record_type) {
+                return Ok(answers);
+            }
+        }
+
+        Err(ResolveError::Timeout(name.to_string()))
+    }
+}
+
+/// Sends a single query to a random DNS server.
+fn query_dns(name: &Name, record_type: RecordType) -> Result<Vec<Record>, ClientError> {
+    // Open Udp connection for DNS client
+    let conn =
+        UdpClientConnection::with_timeout(get_dns_ip(), std::time::Duration::from_millis(200))
+            .unwrap();
+
+    // DNS client stuff
+    let client = SyncClient::new(conn);
+
+    client
+        .query(name, DNSClass::IN, record_type)
+        .map(|dns_response| dns_response.answers().to_owned())
+}
+
+fn get_dns_ip() -> SocketAddr {
     // Choose random DNS server IP from DNS_SERVERS
     let dns_server = DNS_SERVERS.choose(&mut thread_rng()).unwrap();
 
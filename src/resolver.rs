@@ -0,0 +1,117 @@
+use std::fmt;
+use trust_dns_client::rr::{Record, RecordType};
+
+#[cfg(test)]
+use std::collections::HashMap;
+#[cfg(test)]
+use trust_dns_client::rr::{rdata::TXT, Name, RData};
+
+#[derive(Debug, PartialEq)]
+pub enum ResolveError {
+    InvalidName(String),
+    Timeout(String),
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidName(name) => write!(f, "Invalid DNS name `{}`", name),
+            Self::Timeout(name) => write!(f, "No DNS server answered for `{}`", name),
+        }
+    }
+}
+
+/// Blocking DNS lookups for checks that decide what to query next from earlier answers.
+///
+/// An empty answer means the name has no records of that type or does not exist, both of
+/// which count as a void lookup in SPF.
+pub trait Resolver {
+    fn lookup(&self, name: &str, record_type: RecordType) -> Result<Vec<Record>, ResolveError>;
+
+    /// Answers of `record_type` only, dropping the CNAME records that led to them.
+    fn lookup_type(
+        &self,
+        name: &str,
+        record_type: RecordType,
+    ) -> Result<Vec<Record>, ResolveError> {
+        self.lookup(name, record_type).map(|answers| {
+            answers
+                .into_iter()
+                .filter(|r| r.rr_type() == record_type)
+                .collect()
+        })
+    }
+}
+
+/// Resolver answering from a fixed set of records, so checks can be tested offline.
+#[cfg(test)]
+#[derive(Default)]
+pub struct StaticResolver {
+    records: HashMap<(String, RecordType), Vec<Record>>,
+    timeouts: Vec<String>,
+}
+
+#[cfg(test)]
+impl StaticResolver {
+    pub fn with(mut self, name: &str, rdata: RData) -> Self {
+        let record = Record::from_rdata(Name::from_utf8(name).unwrap(), 300, rdata);
+
+        self.records
+            .entry((normalize(name), record.rr_type()))
+            .or_default()
+            .push(record);
+        self
+    }
+
+    pub fn txt(self, name: &str, txt: &str) -> Self {
+        self.with(name, RData::TXT(TXT::new(vec![txt.to_string()])))
+    }
+
+    pub fn a(self, name: &str, ip: &str) -> Self {
+        self.with(name, RData::A(ip.parse().unwrap()))
+    }
+
+    pub fn aaaa(self, name: &str, ip: &str) -> Self {
+        self.with(name, RData::AAAA(ip.parse().unwrap()))
+    }
+
+    pub fn mx(self, name: &str, preference: u16, exchange: &str) -> Self {
+        use trust_dns_client::rr::rdata::MX;
+
+        self.with(
+            name,
+            RData::MX(MX::new(preference, Name::from_utf8(exchange).unwrap())),
+        )
+    }
+
+    pub fn ptr(self, name: &str, target: &str) -> Self {
+        self.with(name, RData::PTR(Name::from_utf8(target).unwrap()))
+    }
+
+    pub fn timeout(mut self, name: &str) -> Self {
+        self.timeouts.push(normalize(name));
+        self
+    }
+}
+
+#[cfg(test)]
+impl Resolver for StaticResolver {
+    fn lookup(&self, name: &str, record_type: RecordType) -> Result<Vec<Record>, ResolveError> {
+        let name = normalize(name);
+
+        if self.timeouts.contains(&name) {
+            return Err(ResolveError::Timeout(name));
+        }
+
+        Ok(self
+            .records
+            .get(&(name, record_type))
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_lowercase()
+}
@@ -0,0 +1,188 @@
+use crate::parse::DmarcFieldResult;
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use serde::Serialize;
+use std::fmt;
+
+const MAX_SCORE: u32 = 100;
+
+const WEIGHT_SEPARATOR: char = ',';
+const VALUE_SEPARATOR: char = '=';
+
+const BAD_WEIGHT: &str = "bad";
+const VERY_BAD_WEIGHT: &str = "very_bad";
+const INVALID_WEIGHT: &str = "invalid";
+const NO_POLICY_WEIGHT: &str = "no_policy";
+
+/// Lowest score for each grade, any score below the last is an F.
+const GRADE_BOUNDARIES: &[(u32, Grade)] = &[
+    (90, Grade::A),
+    (75, Grade::B),
+    (60, Grade::C),
+    (45, Grade::D),
+    (30, Grade::E),
+];
+
+/// Points taken off the maximum score of 100 for each finding.
+#[derive(Debug, PartialEq)]
+pub struct ScoreWeights {
+    pub bad: u32,
+    pub very_bad: u32,
+    pub invalid: u32,
+    pub no_policy: u32,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            bad: 5,
+            very_bad: 20,
+            invalid: 25,
+            no_policy: 100,
+        }
+    }
+}
+
+impl ScoreWeights {
+    /// Parses a comma separated list of `name=points` overriding the default weights, e.g.
+    /// `bad=2,very_bad=30`.
+    pub fn parse(weights: &str) -> Result<Self, String> {
+        let mut score_weights = Self::default();
+
+        for weight in weights.split(WEIGHT_SEPARATOR).map(|w| w.trim()) {
+            if weight.is_empty() {
+                continue;
+            }
+
+            let mut parts = weight.splitn(2, VALUE_SEPARATOR);
+            let (name, points) = match (parts.next(), parts.next()) {
+                (Some(name), Some(points)) => (name.trim(), points.trim()),
+                _ => return Err(format!("Missing `=` in weight `{}`", weight)),
+            };
+
+            let points = points
+                .parse::<u32>()
+                .map_err(|e| format!("Invalid points for weight `{}` - {}", name, e))?;
+
+            match name {
+                BAD_WEIGHT => score_weights.bad = points,
+                VERY_BAD_WEIGHT => score_weights.very_bad = points,
+                INVALID_WEIGHT => score_weights.invalid = points,
+                NO_POLICY_WEIGHT => score_weights.no_policy = points,
+                _ => return Err(format!("Unknown weight `{}`", name)),
+            }
+        }
+
+        Ok(score_weights)
+    }
+
+    /// Points taken off for a single `config_*` result.
+    fn penalty(&self, config_result: &DmarcFieldResult) -> u32 {
+        match config_result {
+            DmarcFieldResult::ValidConfig => 0,
+            DmarcFieldResult::BadConfig(_) => self.bad,
+            DmarcFieldResult::VeryBadConfig(_) => self.very_bad,
+            DmarcFieldResult::InvalidConfig(_) => self.invalid,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum Grade {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+}
+
+impl fmt::Display for Grade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Grade {
+    pub fn from_score(score: u32) -> Self {
+        GRADE_BOUNDARIES
+            .iter()
+            .find(|(min_score, _)| score >= *min_score)
+            .map_or(Self::F, |(_, grade)| *grade)
+    }
+}
+
+/// Combines the `config_*` results of a domain into a score out of 100.
+///
+/// A domain without an applied policy, whether it has no record or a record receivers
+/// discard, loses the `no_policy` points instead.
+pub fn score<'a, I>(has_policy: bool, config_results: I, weights: &ScoreWeights) -> u32
+where
+    I: IntoIterator<Item = &'a DmarcFieldResult>,
+{
+    let penalty: u32 = match has_policy {
+        true => config_results
+            .into_iter()
+            .map(|config_result| weights.penalty(config_result))
+            .sum(),
+        false => weights.no_policy,
+    };
+
+    MAX_SCORE.saturating_sub(penalty)
+}
+
+// Tests
+#[test]
+fn score_weights_parse() {
+    assert_eq!(ScoreWeights::parse(""), Ok(ScoreWeights::default()));
+    assert_eq!(
+        ScoreWeights::parse("bad=2, very_bad = 30,no_policy=90"),
+        Ok(ScoreWeights {
+            bad: 2,
+            very_bad: 30,
+            invalid: 25,
+            no_policy: 90,
+        })
+    );
+    assert_eq!(
+        ScoreWeights::parse("worse=2"),
+        Err("Unknown weight `worse`".to_string())
+    );
+    assert_eq!(
+        ScoreWeights::parse("bad"),
+        Err("Missing `=` in weight `bad`".to_string())
+    );
+    assert!(ScoreWeights::parse("bad=-1").is_err());
+}
+
+#[test]
+fn score_and_grade() {
+    let weights = ScoreWeights::default();
+
+    let valid = DmarcFieldResult::ValidConfig;
+    let invalid = DmarcFieldResult::InvalidConfig("z".to_string());
+
+    assert_eq!(score(true, vec![&valid, &valid], &weights), 100);
+    assert_eq!(
+        score(
+            true,
+            vec![
+                &valid,
+                &DmarcFieldResult::BadConfig("x".to_string()),
+                &DmarcFieldResult::VeryBadConfig("y".to_string()),
+                &invalid,
+            ],
+            &weights
+        ),
+        50
+    );
+    assert_eq!(score(false, vec![], &weights), 0);
+    assert_eq!(score(true, vec![&invalid; 5], &weights), 0);
+
+    assert_eq!(Grade::from_score(100), Grade::A);
+    assert_eq!(Grade::from_score(90), Grade::A);
+    assert_eq!(Grade::from_score(89), Grade::B);
+    assert_eq!(Grade::from_score(50), Grade::D);
+    assert_eq!(Grade::from_score(0), Grade::F);
+    assert_eq!(Grade::from_score(30).to_string(), "E");
+}
@@ -0,0 +1,657 @@
+use crate::parse::join_txt_strings;
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use trust_dns_client::rr::Record;
+
+const SPF1: &str = "v=spf1";
+
+const TERM_SEPARATOR: char = ' ';
+const DOMAIN_SEPARATOR: char = ':';
+const MODIFIER_SEPARATOR: char = '=';
+const CIDR_SEPARATOR: char = '/';
+const MACRO_START: char = '%';
+
+const ALL: &str = "all";
+const INCLUDE: &str = "include";
+const A: &str = "a";
+const MX: &str = "mx";
+const PTR: &str = "ptr";
+const IP4: &str = "ip4";
+const IP6: &str = "ip6";
+const EXISTS: &str = "exists";
+
+const REDIRECT: &str = "redirect";
+const EXP: &str = "exp";
+
+const MAX_IP4_CIDR: u8 = 32;
+const MAX_IP6_CIDR: u8 = 128;
+
+/// Letters allowed in a `%{...}` macro, see RFC 7208 §7.1.
+const MACRO_LETTERS: &str = "slodiphcrtv";
+const MACRO_DELIMITERS: &str = ".-+,/_=";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Qualifier {
+    Pass,
+    Fail,
+    SoftFail,
+    Neutral,
+}
+
+impl fmt::Display for Qualifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Pass => write!(f, "+"),
+            Self::Fail => write!(f, "-"),
+            Self::SoftFail => write!(f, "~"),
+            Self::Neutral => write!(f, "?"),
+        }
+    }
+}
+
+impl Qualifier {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            '+' => Some(Self::Pass),
+            '-' => Some(Self::Fail),
+            '~' => Some(Self::SoftFail),
+            '?' => Some(Self::Neutral),
+            _ => None,
+        }
+    }
+}
+
+/// Prefix lengths of `a` and `mx`, `a/24//64` applies 24 to IPv4 and 64 to IPv6 addresses.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DualCidr {
+    pub ip4: u8,
+    pub ip6: u8,
+}
+
+impl Default for DualCidr {
+    fn default() -> Self {
+        Self {
+            ip4: MAX_IP4_CIDR,
+            ip6: MAX_IP6_CIDR,
+        }
+    }
+}
+
+impl fmt::Display for DualCidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ip4 != MAX_IP4_CIDR {
+            write!(f, "/{}", self.ip4)?;
+        }
+
+        match self.ip6 != MAX_IP6_CIDR {
+            true => write!(f, "//{}", self.ip6),
+            false => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mechanism {
+    All,
+    Include(String),
+    A(Option<String>, DualCidr),
+    Mx(Option<String>, DualCidr),
+    Ptr(Option<String>),
+    Ip4(Ipv4Addr, u8),
+    Ip6(Ipv6Addr, u8),
+    Exists(String),
+}
+
+impl fmt::Display for Mechanism {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let with_domain = |f: &mut fmt::Formatter, name: &str, domain: &Option<String>| match domain
+        {
+            Some(domain) => write!(f, "{}{}{}", name, DOMAIN_SEPARATOR, domain),
+            None => write!(f, "{}", name),
+        };
+
+        match self {
+            Self::All => write!(f, "{}", ALL),
+            Self::Include(domain) => write!(f, "{}{}{}", INCLUDE, DOMAIN_SEPARATOR, domain),
+            Self::A(domain, cidr) => with_domain(f, A, domain).and_then(|_| write!(f, "{}", cidr)),
+            Self::Mx(domain, cidr) => {
+                with_domain(f, MX, domain).and_then(|_| write!(f, "{}", cidr))
+            }
+            Self::Ptr(domain) => with_domain(f, PTR, domain),
+            Self::Ip4(ip, cidr) => write!(f, "{}{}{}/{}", IP4, DOMAIN_SEPARATOR, ip, cidr),
+            Self::Ip6(ip, cidr) => write!(f, "{}{}{}/{}", IP6, DOMAIN_SEPARATOR, ip, cidr),
+            Self::Exists(domain) => write!(f, "{}{}{}", EXISTS, DOMAIN_SEPARATOR, domain),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Directive {
+    pub qualifier: Qualifier,
+    pub mechanism: Mechanism,
+}
+
+impl fmt::Display for Directive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.qualifier {
+            Qualifier::Pass => write!(f, "{}", self.mechanism),
+            q => write!(f, "{}{}", q, self.mechanism),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpfError {
+    UnknownMechanism(String),
+    BadDomainSpec(String),
+    BadIpAddress(String),
+    BadCidrLength(String),
+    DuplicateModifier(String),
+    InvalidTerm(String),
+}
+
+impl fmt::Display for SpfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownMechanism(s) => write!(f, "Unknown mechanism `{}`", s),
+            Self::BadDomainSpec(s) => write!(f, "Bad domain in `{}`", s),
+            Self::BadIpAddress(s) => write!(f, "Bad IP address in `{}`", s),
+            Self::BadCidrLength(s) => write!(f, "Bad CIDR length in `{}`", s),
+            Self::DuplicateModifier(s) => write!(f, "Duplicate modifier `{}`", s),
+            Self::InvalidTerm(s) => write!(f, "Invalid term `{}`", s),
+        }
+    }
+}
+
+/// An SPF record parsed following the RFC 7208 §12 grammar.
+///
+/// Any syntax error makes `check_host` return permerror, parsing still keeps every valid
+/// term so the rest of the record can be reported on.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpfRecord {
+    pub directives: Vec<Directive>,
+    pub redirect: Option<String>,
+    pub exp: Option<String>,
+    pub errors: Vec<SpfError>,
+}
+
+impl SpfRecord {
+    /// Whether `txt` is an SPF record, i.e. it starts with `v=spf1` followed by a space.
+    pub fn is_spf(txt: &str) -> bool {
+        match txt.get(..SPF1.len()) {
+            Some(version) if version.eq_ignore_ascii_case(SPF1) => {
+                txt.len() == SPF1.len() || txt[SPF1.len()..].starts_with(TERM_SEPARATOR)
+            }
+            _ => false,
+        }
+    }
+
+    pub fn parse(txt: &str) -> Self {
+        let mut spf_record = Self::default();
+
+        for term in txt
+            .get(SPF1.len()..)
+            .unwrap_or_default()
+            .split(TERM_SEPARATOR)
+            .filter(|t| !t.is_empty())
+        {
+            spf_record.parse_term(term);
+        }
+
+        spf_record
+    }
+
+    /// The qualifier of the `all` mechanism, which is the result for any host not matched.
+    pub fn all(&self) -> Option<Qualifier> {
+        self.directives
+            .iter()
+            .find(|d| d.mechanism == Mechanism::All)
+            .map(|d| d.qualifier)
+    }
+
+    fn parse_term(&mut self, term: &str) {
+        let name_end = term
+            .find([DOMAIN_SEPARATOR, MODIFIER_SEPARATOR, CIDR_SEPARATOR])
+            .unwrap_or(term.len());
+
+        // A `=` right after the name makes the term a modifier rather than a mechanism
+        if term[name_end..].starts_with(MODIFIER_SEPARATOR) {
+            return self.parse_modifier(term, &term[..name_end], &term[name_end + 1..]);
+        }
+
+        let (qualifier, name) = match term.chars().next().and_then(Qualifier::from_char) {
+            Some(q) => (q, &term[1..name_end]),
+            None => (Qualifier::Pass, &term[..name_end]),
+        };
+
+        match parse_mechanism(term, &name.to_lowercase(), &term[name_end..]) {
+            Ok(mechanism) => self.directives.push(Directive {
+                qualifier,
+                mechanism,
+            }),
+            Err(e) => self.errors.push(e),
+        }
+    }
+
+    fn parse_modifier(&mut self, term: &str, name: &str, value: &str) {
+        let target = match name.to_lowercase().as_str() {
+            REDIRECT => &mut self.redirect,
+            EXP => &mut self.exp,
+            // Unknown modifiers must be ignored, but still follow the grammar
+            _ => {
+                if !is_modifier_name(name) || !is_macro_string(value) {
+                    self.errors.push(SpfError::InvalidTerm(term.to_string()));
+                }
+                return;
+            }
+        };
+
+        if target.is_some() {
+            self.errors
+                .push(SpfError::DuplicateModifier(name.to_lowercase()));
+        } else if !is_domain_spec(value) {
+            self.errors.push(SpfError::BadDomainSpec(term.to_string()));
+        } else {
+            *target = Some(value.to_string());
+        }
+    }
+}
+
+impl fmt::Display for SpfRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", SPF1)?;
+
+        for directive in &self.directives {
+            write!(f, "{}{}", TERM_SEPARATOR, directive)?;
+        }
+
+        if let Some(redirect) = &self.redirect {
+            write!(f, "{}{}={}", TERM_SEPARATOR, REDIRECT, redirect)?;
+        }
+
+        match &self.exp {
+            Some(exp) => write!(f, "{}{}={}", TERM_SEPARATOR, EXP, exp),
+            None => Ok(()),
+        }
+    }
+}
+
+fn parse_mechanism(term: &str, name: &str, rest: &str) -> Result<Mechanism, SpfError> {
+    let domain = rest.strip_prefix(DOMAIN_SEPARATOR);
+    let required_domain = || match domain {
+        Some(d) if is_domain_spec(d) => Ok(d.to_string()),
+        _ => Err(SpfError::BadDomainSpec(term.to_string())),
+    };
+
+    match name {
+        ALL if rest.is_empty() => Ok(Mechanism::All),
+        INCLUDE => required_domain().map(Mechanism::Include),
+        EXISTS => required_domain().map(Mechanism::Exists),
+        PTR => match domain {
+            None if rest.is_empty() => Ok(Mechanism::Ptr(None)),
+            _ => required_domain().map(|d| Mechanism::Ptr(Some(d))),
+        },
+        A | MX => {
+            let (domain, cidr) = split_dual_cidr(term, rest)?;
+            let domain = match domain {
+                Some(d) if is_domain_spec(d) => Some(d.to_string()),
+                Some(_) => return Err(SpfError::BadDomainSpec(term.to_string())),
+                None => None,
+            };
+
+            Ok(match name {
+                A => Mechanism::A(domain, cidr),
+                _ => Mechanism::Mx(domain, cidr),
+            })
+        }
+        IP4 => {
+            let (ip, cidr) = split_cidr(term, domain, MAX_IP4_CIDR)?;
+            ip.parse::<Ipv4Addr>()
+                .map(|ip| Mechanism::Ip4(ip, cidr))
+                .map_err(|_| SpfError::BadIpAddress(term.to_string()))
+        }
+        IP6 => {
+            let (ip, cidr) = split_cidr(term, domain, MAX_IP6_CIDR)?;
+            ip.parse::<Ipv6Addr>()
+                .map(|ip| Mechanism::Ip6(ip, cidr))
+                .map_err(|_| SpfError::BadIpAddress(term.to_string()))
+        }
+        ALL => Err(SpfError::InvalidTerm(term.to_string())),
+        _ => Err(SpfError::UnknownMechanism(term.to_string())),
+    }
+}
+
+/// Splits `:network/cidr` into the network and a prefix length no longer than `max`.
+fn split_cidr<'a>(
+    term: &str,
+    network: Option<&'a str>,
+    max: u8,
+) -> Result<(&'a str, u8), SpfError> {
+    let network = network.ok_or_else(|| SpfError::BadIpAddress(term.to_string()))?;
+
+    match network.find(CIDR_SEPARATOR) {
+        Some(i) => parse_cidr_length(term, &network[i + 1..], max).map(|c| (&network[..i], c)),
+        None => Ok((network, max)),
+    }
+}
+
+/// Splits `[:domain][/ip4-cidr][//ip6-cidr]` of an `a` or `mx` mechanism.
+fn split_dual_cidr<'a>(term: &str, rest: &'a str) -> Result<(Option<&'a str>, DualCidr), SpfError> {
+    let (domain, cidrs) = match rest.find(CIDR_SEPARATOR) {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, ""),
+    };
+
+    let domain = match domain.strip_prefix(DOMAIN_SEPARATOR) {
+        Some(d) => Some(d),
+        None if domain.is_empty() => None,
+        None => return Err(SpfError::UnknownMechanism(term.to_string())),
+    };
+
+    let mut cidr = DualCidr::default();
+
+    let ip6 = match cidrs.find("//") {
+        Some(i) => {
+            cidr.ip6 = parse_cidr_length(term, &cidrs[i + 2..], MAX_IP6_CIDR)?;
+            &cidrs[..i]
+        }
+        None => cidrs,
+    };
+
+    if let Some(ip4) = ip6.strip_prefix(CIDR_SEPARATOR) {
+        cidr.ip4 = parse_cidr_length(term, ip4, MAX_IP4_CIDR)?;
+    } else if !ip6.is_empty() {
+        return Err(SpfError::BadCidrLength(term.to_string()));
+    }
+
+    Ok((domain, cidr))
+}
+
+fn parse_cidr_length(term: &str, length: &str, max: u8) -> Result<u8, SpfError> {
+    match length.parse::<u8>() {
+        // Leading zeros are not allowed by the grammar
+        Ok(l) if l <= max && !(length.len() > 1 && length.starts_with('0')) => Ok(l),
+        _ => Err(SpfError::BadCidrLength(term.to_string())),
+    }
+}
+
+/// name = ALPHA *( ALPHA / DIGIT / "-" / "_" / "." )
+fn is_modifier_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() => {
+            chars.all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        }
+        _ => false,
+    }
+}
+
+/// domain-spec = macro-string domain-end, where the domain end is either a macro or a
+/// top label that is not all numeric.
+fn is_domain_spec(domain_spec: &str) -> bool {
+    if domain_spec.is_empty() || !is_macro_string(domain_spec) {
+        return false;
+    }
+
+    if domain_spec.trim_end_matches('.').ends_with('}') {
+        return true;
+    }
+
+    let top_label = domain_spec
+        .trim_end_matches('.')
+        .rsplit('.')
+        .next()
+        .unwrap_or_default();
+
+    domain_spec.contains('.')
+        && !top_label.is_empty()
+        && !top_label.chars().all(|c| c.is_ascii_digit())
+        && top_label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && !top_label.starts_with('-')
+        && !top_label.ends_with('-')
+}
+
+/// macro-string = *( macro-expand / macro-literal ), see RFC 7208 §7.1.
+pub fn is_macro_string(s: &str) -> bool {
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != MACRO_START {
+            // macro-literal = %x21-24 / %x26-7E
+            if !('\x21'..='\x7e').contains(&c) {
+                return false;
+            }
+            continue;
+        }
+
+        match chars.next() {
+            Some('%') | Some('_') | Some('-') => (),
+            Some('{') => {
+                match chars.next() {
+                    Some(l) if MACRO_LETTERS.contains(l.to_ascii_lowercase()) => (),
+                    _ => return false,
+                }
+
+                while chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    chars.next();
+                }
+
+                if chars.peek().is_some_and(|c| c.eq_ignore_ascii_case(&'r')) {
+                    chars.next();
+                }
+
+                while chars.peek().is_some_and(|c| MACRO_DELIMITERS.contains(*c)) {
+                    chars.next();
+                }
+
+                if chars.next() != Some('}') {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SpfFinding {
+    NoRecord,
+    MultipleRecords(usize),
+    SyntaxError(String),
+    PassAll,
+    NeutralAll,
+    SoftFailAll,
+    NoAll,
+    Ptr,
+}
+
+impl fmt::Display for SpfFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoRecord => write!(f, "No SPF record"),
+            Self::MultipleRecords(n) => write!(f, "{} SPF records: permerror", n),
+            Self::SyntaxError(s) => write!(f, "{}", s),
+            Self::PassAll => write!(f, "+all: Any host may send mail"),
+            Self::NeutralAll => write!(f, "?all: No assertion about other hosts"),
+            Self::SoftFailAll => write!(f, "~all: Other hosts only soft fail"),
+            Self::NoAll => write!(f, "No `all` or `redirect`: Other hosts are neutral"),
+            Self::Ptr => write!(f, "ptr: Deprecated, see RFC 7208 §5.5"),
+        }
+    }
+}
+
+impl SpfFinding {
+    /// Findings that make receivers return permerror for the whole record.
+    pub fn is_invalid(&self) -> bool {
+        matches!(self, Self::MultipleRecords(_) | Self::SyntaxError(_))
+    }
+
+    /// Findings that let any host pass or go unjudged.
+    pub fn is_very_bad(&self) -> bool {
+        matches!(self, Self::PassAll)
+    }
+}
+
+/// The SPF record published at a domain and what is wrong with it.
+#[derive(Debug, Default, PartialEq)]
+pub struct SpfCheck {
+    pub raw_txt: Option<String>,
+    pub record: Option<SpfRecord>,
+    pub findings: Vec<SpfFinding>,
+}
+
+impl SpfCheck {
+    /// Selects the SPF record from the TXT answers at a domain, see RFC 7208 §4.5.
+    pub fn from_answers(r: &[Record]) -> Self {
+        let spf_txts: Vec<String> = r
+            .iter()
+            .filter_map(|record| record.rdata().as_txt())
+            .map(join_txt_strings)
+            .filter(|txt| SpfRecord::is_spf(txt))
+            .collect();
+
+        match spf_txts.len() {
+            0 => Self {
+                findings: vec![SpfFinding::NoRecord],
+                ..Self::default()
+            },
+            1 => Self::from_txt(&spf_txts[0]),
+            n => Self {
+                raw_txt: Some(spf_txts.join(" | ")),
+                findings: vec![SpfFinding::MultipleRecords(n)],
+                ..Self::default()
+            },
+        }
+    }
+
+    pub fn from_txt(txt: &str) -> Self {
+        let record = SpfRecord::parse(txt);
+        let mut findings: Vec<SpfFinding> = record
+            .errors
+            .iter()
+            .map(|e| SpfFinding::SyntaxError(e.to_string()))
+            .collect();
+
+        match record.all() {
+            Some(Qualifier::Pass) => findings.push(SpfFinding::PassAll),
+            Some(Qualifier::Neutral) => findings.push(SpfFinding::NeutralAll),
+            Some(Qualifier::SoftFail) => findings.push(SpfFinding::SoftFailAll),
+            Some(Qualifier::Fail) => (),
+            None if record.redirect.is_none() => findings.push(SpfFinding::NoAll),
+            None => (),
+        }
+
+        if record
+            .directives
+            .iter()
+            .any(|d| matches!(d.mechanism, Mechanism::Ptr(_)))
+        {
+            findings.push(SpfFinding::Ptr);
+        }
+
+        Self {
+            raw_txt: Some(txt.to_string()),
+            record: Some(record),
+            findings,
+        }
+    }
+}
+
+// Tests
+#[test]
+fn spf_record_parse() {
+    assert!(SpfRecord::is_spf("v=spf1"));
+    assert!(SpfRecord::is_spf("V=SPF1 -all"));
+    assert!(!SpfRecord::is_spf("v=spf10 -all"));
+    assert!(!SpfRecord::is_spf("v=DMARC1; p=none"));
+
+    let spf_record = SpfRecord::parse(
+        "v=spf1 ip4:192.0.2.0/24 ip6:2001:db8::/32 a mx:mail.example.com/28//64 \
+         include:_spf.%{d} ~ptr exists:%{ir}.%{l1r+-}._spf.%{d} redirect=_spf.example.com -all",
+    );
+    assert_eq!(spf_record.errors, vec![]);
+    assert_eq!(
+        spf_record.directives[..2],
+        [
+            Directive {
+                qualifier: Qualifier::Pass,
+                mechanism: Mechanism::Ip4(Ipv4Addr::new(192, 0, 2, 0), 24),
+            },
+            Directive {
+                qualifier: Qualifier::Pass,
+                mechanism: Mechanism::Ip6("2001:db8::".parse().unwrap(), 32),
+            },
+        ]
+    );
+    assert_eq!(
+        spf_record.directives[3].mechanism,
+        Mechanism::Mx(
+            Some("mail.example.com".to_string()),
+            DualCidr { ip4: 28, ip6: 64 }
+        )
+    );
+    assert_eq!(spf_record.redirect, Some("_spf.example.com".to_string()));
+    assert_eq!(spf_record.all(), Some(Qualifier::Fail));
+    assert_eq!(
+        spf_record.to_string(),
+        "v=spf1 ip4:192.0.2.0/24 ip6:2001:db8::/32 a mx:mail.example.com/28//64 \
+         include:_spf.%{d} ~ptr exists:%{ir}.%{l1r+-}._spf.%{d} -all redirect=_spf.example.com"
+    );
+}
+
+#[test]
+fn spf_record_parse_errors() {
+    let spf_record = SpfRecord::parse(
+        "v=spf1 ip4:192.0.2.300 ip4:192.0.2.0/33 include: a:example.com/024 foo:bar \
+         redirect=a.example.com redirect=b.example.com exp=%{x} all:x unknown=ok",
+    );
+    assert_eq!(
+        spf_record.errors,
+        vec![
+            SpfError::BadIpAddress("ip4:192.0.2.300".to_string()),
+            SpfError::BadCidrLength("ip4:192.0.2.0/33".to_string()),
+            SpfError::BadDomainSpec("include:".to_string()),
+            SpfError::BadCidrLength("a:example.com/024".to_string()),
+            SpfError::UnknownMechanism("foo:bar".to_string()),
+            SpfError::DuplicateModifier("redirect".to_string()),
+            SpfError::BadDomainSpec("exp=%{x}".to_string()),
+            SpfError::InvalidTerm("all:x".to_string()),
+        ]
+    );
+    assert_eq!(spf_record.directives, vec![]);
+}
+
+#[test]
+fn spf_check_findings() {
+    let spf_check = SpfCheck::from_txt("v=spf1 ptr ?all");
+    assert_eq!(
+        spf_check.findings,
+        vec![SpfFinding::NeutralAll, SpfFinding::Ptr]
+    );
+
+    assert_eq!(
+        SpfCheck::from_txt("v=spf1 +all").findings,
+        vec![SpfFinding::PassAll]
+    );
+    assert_eq!(
+        SpfCheck::from_txt("v=spf1 mx").findings,
+        vec![SpfFinding::NoAll]
+    );
+    assert_eq!(
+        SpfCheck::from_txt("v=spf1 redirect=_spf.example.com").findings,
+        vec![]
+    );
+    assert_eq!(
+        SpfCheck::from_txt("v=spf1 ip4:192.0.2.1 mx:example -all").findings,
+        vec![SpfFinding::SyntaxError(
+            "Bad domain in `mx:example`".to_string()
+        )]
+    );
+}
@@ -0,0 +1,365 @@
+use crate::parse::join_txt_strings;
+use crate::resolver::Resolver;
+#[cfg(test)]
+use crate::resolver::StaticResolver;
+use crate::spf::{Mechanism, SpfRecord};
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use std::fmt;
+use trust_dns_client::rr::RecordType;
+
+/// Mechanisms and modifiers that query DNS are limited to 10 per check, RFC 7208 §4.6.4.
+pub const MAX_DNS_LOOKUPS: usize = 10;
+pub const MAX_VOID_LOOKUPS: usize = 2;
+pub const MAX_MX_NAMES: usize = 10;
+
+/// Lookups after which expansion stops, well past the limit so the total is still useful.
+const MAX_EXPANDED_LOOKUPS: usize = 100;
+
+const MACRO_START: char = '%';
+
+#[derive(Debug, PartialEq)]
+pub enum Via {
+    Record,
+    Include,
+    Redirect,
+}
+
+/// A record in the include tree and the lookups its own terms cost.
+#[derive(Debug, PartialEq)]
+pub struct SpfNode {
+    pub domain: String,
+    pub via: Via,
+    pub lookups: usize,
+    pub children: Vec<SpfNode>,
+}
+
+impl fmt::Display for SpfNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.via == Via::Redirect {
+            write!(f, "redirect=")?;
+        }
+
+        write!(f, "{} ({})", self.domain, self.lookups)?;
+
+        if self.children.is_empty() {
+            return Ok(());
+        }
+
+        write!(f, " [")?;
+        for (i, child) in self.children.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", child)?;
+        }
+        write!(f, "]")
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ExpansionFinding {
+    TooManyLookups(usize),
+    TooManyVoidLookups(usize),
+    Loop(String),
+    NoRecord(String),
+    MultipleRecords(String, usize),
+    SyntaxError(String, String),
+    TooManyMxNames(String, usize),
+    Timeout(String),
+}
+
+impl fmt::Display for ExpansionFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TooManyLookups(n) => write!(
+                f,
+                "{} DNS lookups, receivers return permerror above {}",
+                n, MAX_DNS_LOOKUPS
+            ),
+            Self::TooManyVoidLookups(n) => write!(
+                f,
+                "{} void lookups, receivers may return permerror above {}",
+                n, MAX_VOID_LOOKUPS
+            ),
+            Self::Loop(domain) => write!(f, "Include loop at `{}`", domain),
+            Self::NoRecord(domain) => write!(f, "No SPF record at `{}`", domain),
+            Self::MultipleRecords(domain, n) => write!(f, "{} SPF records at `{}`", n, domain),
+            Self::SyntaxError(domain, e) => write!(f, "{} at `{}`", e, domain),
+            Self::TooManyMxNames(domain, n) => write!(
+                f,
+                "{} MX names at `{}`, receivers return permerror above {}",
+                n, domain, MAX_MX_NAMES
+            ),
+            Self::Timeout(domain) => write!(f, "DNS timeout at `{}`", domain),
+        }
+    }
+}
+
+impl ExpansionFinding {
+    /// Findings that make receivers return permerror rather than temperror.
+    pub fn is_invalid(&self) -> bool {
+        !matches!(self, Self::Timeout(_))
+    }
+}
+
+/// Every record reached from a domain's SPF record and the DNS lookups they cost.
+///
+/// Terms using macros depend on the message, so they are counted but not resolved, as are
+/// `ptr` terms which need the sender IP.
+#[derive(Debug, PartialEq)]
+pub struct SpfExpansion {
+    pub tree: SpfNode,
+    pub lookups: usize,
+    pub void_lookups: usize,
+    pub findings: Vec<ExpansionFinding>,
+}
+
+impl SpfNode {
+    fn new(domain: &str, via: Via) -> Self {
+        Self {
+            domain: normalize(domain),
+            via,
+            lookups: 0,
+            children: Vec::new(),
+        }
+    }
+}
+
+impl SpfExpansion {
+    pub fn expand<R: Resolver>(resolver: &R, domain: &str, spf_record: &SpfRecord) -> Self {
+        let mut tree = SpfNode::new(domain, Via::Record);
+        let mut expander = Expander {
+            resolver,
+            lookups: 0,
+            void_lookups: 0,
+            findings: Vec::new(),
+        };
+
+        expander.visit(&mut tree, spf_record, &mut vec![normalize(domain)]);
+
+        if expander.lookups > MAX_DNS_LOOKUPS {
+            expander
+                .findings
+                .push(ExpansionFinding::TooManyLookups(expander.lookups));
+        }
+
+        if expander.void_lookups > MAX_VOID_LOOKUPS {
+            expander
+                .findings
+                .push(ExpansionFinding::TooManyVoidLookups(expander.void_lookups));
+        }
+
+        Self {
+            tree,
+            lookups: expander.lookups,
+            void_lookups: expander.void_lookups,
+            findings: expander.findings,
+        }
+    }
+}
+
+struct Expander<'a, R: Resolver> {
+    resolver: &'a R,
+    lookups: usize,
+    void_lookups: usize,
+    findings: Vec<ExpansionFinding>,
+}
+
+impl<'a, R: Resolver> Expander<'a, R> {
+    fn visit(&mut self, node: &mut SpfNode, spf_record: &SpfRecord, path: &mut Vec<String>) {
+        for directive in &spf_record.directives {
+            if self.lookups >= MAX_EXPANDED_LOOKUPS {
+                return;
+            }
+
+            match &directive.mechanism {
+                Mechanism::All | Mechanism::Ip4(..) | Mechanism::Ip6(..) => continue,
+                Mechanism::Include(target) => {
+                    self.count(node);
+                    self.follow(node, target, Via::Include, path);
+                }
+                Mechanism::A(target, _) => {
+                    self.count(node);
+                    let target = target.as_deref().unwrap_or(&node.domain).to_string();
+                    self.resolve(&target, &[RecordType::A, RecordType::AAAA]);
+                }
+                Mechanism::Mx(target, _) => {
+                    self.count(node);
+                    let target = target.as_deref().unwrap_or(&node.domain).to_string();
+                    let mx_names = self.resolve(&target, &[RecordType::MX]);
+
+                    if mx_names > MAX_MX_NAMES {
+                        self.findings.push(ExpansionFinding::TooManyMxNames(
+                            normalize(&target),
+                            mx_names,
+                        ));
+                    }
+                }
+                Mechanism::Exists(target) => {
+                    self.count(node);
+                    self.resolve(target, &[RecordType::A]);
+                }
+                Mechanism::Ptr(_) => self.count(node),
+            }
+        }
+
+        // redirect is only used when no `all` mechanism matches every other host
+        if let (Some(target), None) = (&spf_record.redirect, spf_record.all()) {
+            self.count(node);
+            self.follow(node, target, Via::Redirect, path);
+        }
+    }
+
+    fn count(&mut self, node: &mut SpfNode) {
+        self.lookups += 1;
+        node.lookups += 1;
+    }
+
+    /// Fetches and expands the SPF record of an `include` or `redirect` target.
+    fn follow(&mut self, node: &mut SpfNode, target: &str, via: Via, path: &mut Vec<String>) {
+        if target.contains(MACRO_START) {
+            return;
+        }
+
+        let target = normalize(target);
+
+        if path.contains(&target) {
+            self.findings.push(ExpansionFinding::Loop(target));
+            return;
+        }
+
+        let answers = match self.resolver.lookup_type(&target, RecordType::TXT) {
+            Ok(answers) => answers,
+            Err(_) => {
+                self.findings.push(ExpansionFinding::Timeout(target));
+                return;
+            }
+        };
+
+        let spf_txts: Vec<String> = answers
+            .iter()
+            .filter_map(|r| r.rdata().as_txt())
+            .map(join_txt_strings)
+            .filter(|txt| SpfRecord::is_spf(txt))
+            .collect();
+
+        let spf_record = match spf_txts.len() {
+            0 => {
+                if answers.is_empty() {
+                    self.void_lookups += 1;
+                }
+                self.findings.push(ExpansionFinding::NoRecord(target));
+                return;
+            }
+            1 => SpfRecord::parse(&spf_txts[0]),
+            n => {
+                self.findings
+                    .push(ExpansionFinding::MultipleRecords(target, n));
+                return;
+            }
+        };
+
+        if let Some(e) = spf_record.errors.first() {
+            self.findings
+                .push(ExpansionFinding::SyntaxError(target.clone(), e.to_string()));
+        }
+
+        let mut child = SpfNode::new(&target, via);
+
+        path.push(target);
+        self.visit(&mut child, &spf_record, path);
+        path.pop();
+
+        node.children.push(child);
+    }
+
+    /// Looks up `target` for each record type until one has answers, returning their number.
+    fn resolve(&mut self, target: &str, types: &[RecordType]) -> usize {
+        if target.contains(MACRO_START) {
+            return 0;
+        }
+
+        for record_type in types {
+            match self.resolver.lookup_type(target, *record_type) {
+                Ok(answers) if !answers.is_empty() => return answers.len(),
+                Ok(_) => (),
+                Err(_) => {
+                    self.findings
+                        .push(ExpansionFinding::Timeout(normalize(target)));
+                    return 0;
+                }
+            }
+        }
+
+        self.void_lookups += 1;
+        0
+    }
+}
+
+fn normalize(domain: &str) -> String {
+    domain.trim_end_matches('.').to_lowercase()
+}
+
+// Tests
+#[test]
+fn spf_expansion_include_tree() {
+    let resolver = StaticResolver::default()
+        .txt(
+            "_spf.example.net",
+            "v=spf1 include:_a.example.net include:_b.example.net ~all",
+        )
+        .txt("_a.example.net", "v=spf1 ip4:192.0.2.0/24 -all")
+        .txt("_b.example.net", "v=spf1 a:mail.example.net -all")
+        .a("mail.example.net", "192.0.2.25")
+        .mx("example.com", 10, "mail.example.net")
+        .txt("_spf.example.com", "v=spf1 exists:%{i}._ip.example.com")
+        .aaaa("example.com", "2001:db8::25");
+
+    let spf_record =
+        SpfRecord::parse("v=spf1 a mx include:_spf.example.net ptr redirect=_spf.example.com");
+    let expansion = SpfExpansion::expand(&resolver, "Example.com", &spf_record);
+
+    assert_eq!(expansion.findings, vec![]);
+    assert_eq!(expansion.lookups, 9);
+    assert_eq!(expansion.void_lookups, 0);
+    assert_eq!(
+        expansion.tree.to_string(),
+        "example.com (5) [_spf.example.net (2) [_a.example.net (0), _b.example.net (1)], \
+         redirect=_spf.example.com (1)]"
+    );
+}
+
+#[test]
+fn spf_expansion_limits() {
+    let mut resolver = StaticResolver::default()
+        .txt("loop.example.com", "v=spf1 include:example.com -all")
+        .txt(
+            "void.example.com",
+            "v=spf1 a:x.example.com exists:y.example.com mx -all",
+        )
+        .timeout("slow.example.com");
+    for i in 0..MAX_MX_NAMES + 1 {
+        resolver = resolver.mx("void.example.com", 10, &format!("mx{}.example.com", i));
+    }
+
+    let spf_record = SpfRecord::parse(
+        "v=spf1 include:loop.example.com include:void.example.com include:none.example.com \
+         include:slow.example.com a:a1.example.com a:a2.example.com a:a3.example.com -all",
+    );
+    let expansion = SpfExpansion::expand(&resolver, "example.com", &spf_record);
+
+    assert_eq!(expansion.lookups, 11);
+    assert_eq!(expansion.void_lookups, 6);
+    assert_eq!(
+        expansion.findings,
+        vec![
+            ExpansionFinding::Loop("example.com".to_string()),
+            ExpansionFinding::TooManyMxNames("void.example.com".to_string(), 11),
+            ExpansionFinding::NoRecord("none.example.com".to_string()),
+            ExpansionFinding::Timeout("slow.example.com".to_string()),
+            ExpansionFinding::TooManyLookups(11),
+            ExpansionFinding::TooManyVoidLookups(6),
+        ]
+    );
+}
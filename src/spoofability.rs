@@ -0,0 +1,279 @@
+use crate::spf::Qualifier;
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use std::fmt;
+
+const FULL_PCT: u8 = 100;
+
+const REASON_NO_POLICY: &str = "No DMARC policy applies";
+const REASON_POLICY_NONE: &str = "DMARC policy is `none`";
+const REASON_TESTING: &str = "t=y: Receivers apply a policy one level less strict";
+const REASON_QUARANTINE: &str = "Quarantine delivers spoofed mail to spam";
+const REASON_SP_NONE: &str = "sp=none: Subdomains can be spoofed";
+const REASON_NP_NONE: &str = "np=none: Non-existent subdomains can be spoofed";
+const REASON_NO_SPF: &str = "No valid SPF record";
+const REASON_SPF_NO_ALL: &str = "SPF record has no `all`, other hosts are neutral";
+const REASON_SPF_ALL_UNKNOWN: &str = "SPF redirect target could not be resolved to an `all`";
+const REASON_SPF_WEAK_ALL: &str = "SPF passes or is neutral for any host with";
+const REASON_SPF_ONLY: &str = "Only receivers acting on SPF alone reject spoofed mail with";
+const REASON_NO_MAIL_HOST: &str =
+    "No mail host: Receivers that check the domain can receive mail reject it";
+
+/// The action DMARC tells receivers to take on mail failing authentication.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Enforcement {
+    None,
+    Quarantine,
+    Reject,
+}
+
+/// What receivers learn from DNS when deciding whether mail claiming a domain is genuine.
+#[derive(Debug, PartialEq)]
+pub struct Exposure {
+    /// Applied DMARC policy, `None` when no record or no valid policy applies.
+    pub policy: Option<Enforcement>,
+    pub pct: u8,
+    pub testing: bool,
+    pub subdomain_policy: Option<Enforcement>,
+    pub non_existent_policy: Option<Enforcement>,
+    pub has_spf: bool,
+    /// Qualifier of the `all` that ends the record, following any `redirect`.
+    pub spf_all: Option<Qualifier>,
+    /// The record has a `redirect` whose target's `all` is not known.
+    pub spf_all_unknown: bool,
+    /// An MX host, or the implicit MX of a domain with an address and no MX records.
+    pub receives_mail: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verdict {
+    DirectlySpoofable,
+    SpoofableToLenientReceivers,
+    SubdomainsSpoofable,
+    Protected,
+    Unknown,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DirectlySpoofable => write!(f, "Directly spoofable"),
+            Self::SpoofableToLenientReceivers => write!(f, "Spoofable to lenient receivers"),
+            Self::SubdomainsSpoofable => write!(f, "Subdomains spoofable"),
+            Self::Protected => write!(f, "Protected"),
+            Self::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+/// Whether mail can be sent claiming to be from a domain, and why.
+#[derive(Debug, PartialEq)]
+pub struct Spoofability {
+    pub verdict: Verdict,
+    pub reasons: Vec<String>,
+}
+
+impl Spoofability {
+    /// Combines the DMARC policy, SPF and MX of a domain into a single verdict.
+    ///
+    /// An enforced DMARC policy decides the verdict. Without one, a strict SPF `all` or a
+    /// domain that cannot receive mail only stops receivers that check these on their own.
+    pub fn assess(exposure: &Exposure) -> Self {
+        let mut reasons: Vec<String> = Vec::new();
+
+        let enforcement = match exposure.policy {
+            Some(Enforcement::None) => {
+                reasons.push(REASON_POLICY_NONE.to_string());
+                None
+            }
+            Some(enforcement) => Some(enforcement),
+            None => {
+                reasons.push(REASON_NO_POLICY.to_string());
+                None
+            }
+        };
+
+        let verdict = match enforcement {
+            Some(enforcement) => Self::enforced_verdict(exposure, enforcement, &mut reasons),
+            None => Self::unenforced_verdict(exposure, &mut reasons),
+        };
+
+        Self { verdict, reasons }
+    }
+
+    fn enforced_verdict(
+        exposure: &Exposure,
+        enforcement: Enforcement,
+        reasons: &mut Vec<String>,
+    ) -> Verdict {
+        if enforcement == Enforcement::Quarantine {
+            reasons.push(REASON_QUARANTINE.to_string());
+        }
+
+        if exposure.pct < FULL_PCT {
+            reasons.push(format!(
+                "pct={}: The policy applies to {}% of failing mail",
+                exposure.pct, exposure.pct
+            ));
+        }
+
+        if exposure.testing {
+            reasons.push(REASON_TESTING.to_string());
+        }
+
+        if exposure.pct < FULL_PCT || exposure.testing {
+            return Verdict::SpoofableToLenientReceivers;
+        }
+
+        if exposure.subdomain_policy == Some(Enforcement::None) {
+            reasons.push(REASON_SP_NONE.to_string());
+        }
+
+        if exposure.non_existent_policy == Some(Enforcement::None) {
+            reasons.push(REASON_NP_NONE.to_string());
+        }
+
+        match exposure.subdomain_policy == Some(Enforcement::None)
+            || exposure.non_existent_policy == Some(Enforcement::None)
+        {
+            true => Verdict::SubdomainsSpoofable,
+            false => Verdict::Protected,
+        }
+    }
+
+    fn unenforced_verdict(exposure: &Exposure, reasons: &mut Vec<String>) -> Verdict {
+        match (exposure.has_spf, exposure.spf_all) {
+            (false, _) => reasons.push(REASON_NO_SPF.to_string()),
+            (true, None) if exposure.spf_all_unknown => {
+                reasons.push(REASON_SPF_ALL_UNKNOWN.to_string());
+                return Verdict::Unknown;
+            }
+            (true, None) => reasons.push(REASON_SPF_NO_ALL.to_string()),
+            (true, Some(q @ Qualifier::Pass)) | (true, Some(q @ Qualifier::Neutral)) => {
+                reasons.push(format!("{} `{}all`", REASON_SPF_WEAK_ALL, q))
+            }
+            (true, Some(q)) => {
+                reasons.push(format!("{} `{}all`", REASON_SPF_ONLY, q));
+                return Verdict::SpoofableToLenientReceivers;
+            }
+        }
+
+        match exposure.receives_mail {
+            true => Verdict::DirectlySpoofable,
+            false => {
+                reasons.push(REASON_NO_MAIL_HOST.to_string());
+                Verdict::SpoofableToLenientReceivers
+            }
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+fn exposure(policy: Option<Enforcement>, spf_all: Option<Qualifier>) -> Exposure {
+    Exposure {
+        policy,
+        pct: FULL_PCT,
+        testing: false,
+        subdomain_policy: policy,
+        non_existent_policy: None,
+        has_spf: spf_all.is_some(),
+        spf_all,
+        spf_all_unknown: false,
+        receives_mail: true,
+    }
+}
+
+#[test]
+fn spoofability_unenforced() {
+    assert_eq!(
+        Spoofability::assess(&exposure(None, None)),
+        Spoofability {
+            verdict: Verdict::DirectlySpoofable,
+            reasons: vec![REASON_NO_POLICY.to_string(), REASON_NO_SPF.to_string()],
+        }
+    );
+    assert_eq!(
+        Spoofability::assess(&exposure(Some(Enforcement::None), Some(Qualifier::Neutral))),
+        Spoofability {
+            verdict: Verdict::DirectlySpoofable,
+            reasons: vec![
+                REASON_POLICY_NONE.to_string(),
+                format!("{} `?all`", REASON_SPF_WEAK_ALL),
+            ],
+        }
+    );
+    assert_eq!(
+        Spoofability::assess(&exposure(Some(Enforcement::None), Some(Qualifier::Fail))).verdict,
+        Verdict::SpoofableToLenientReceivers
+    );
+
+    let no_mx = Exposure {
+        receives_mail: false,
+        ..exposure(None, None)
+    };
+    assert_eq!(
+        Spoofability::assess(&no_mx).verdict,
+        Verdict::SpoofableToLenientReceivers
+    );
+
+    let unresolved_redirect = Exposure {
+        has_spf: true,
+        spf_all_unknown: true,
+        ..exposure(Some(Enforcement::None), None)
+    };
+    assert_eq!(
+        Spoofability::assess(&unresolved_redirect),
+        Spoofability {
+            verdict: Verdict::Unknown,
+            reasons: vec![
+                REASON_POLICY_NONE.to_string(),
+                REASON_SPF_ALL_UNKNOWN.to_string(),
+            ],
+        }
+    );
+}
+
+#[test]
+fn spoofability_enforced() {
+    assert_eq!(
+        Spoofability::assess(&exposure(Some(Enforcement::Reject), None)),
+        Spoofability {
+            verdict: Verdict::Protected,
+            reasons: vec![],
+        }
+    );
+    assert_eq!(
+        Spoofability::assess(&exposure(Some(Enforcement::Quarantine), None)),
+        Spoofability {
+            verdict: Verdict::Protected,
+            reasons: vec![REASON_QUARANTINE.to_string()],
+        }
+    );
+
+    let partial = Exposure {
+        pct: 20,
+        subdomain_policy: Some(Enforcement::None),
+        ..exposure(Some(Enforcement::Reject), None)
+    };
+    assert_eq!(
+        Spoofability::assess(&partial),
+        Spoofability {
+            verdict: Verdict::SpoofableToLenientReceivers,
+            reasons: vec!["pct=20: The policy applies to 20% of failing mail".to_string()],
+        }
+    );
+
+    let subdomains = Exposure {
+        subdomain_policy: Some(Enforcement::None),
+        non_existent_policy: Some(Enforcement::None),
+        ..exposure(Some(Enforcement::Reject), None)
+    };
+    assert_eq!(
+        Spoofability::assess(&subdomains),
+        Spoofability {
+            verdict: Verdict::SubdomainsSpoofable,
+            reasons: vec![REASON_SP_NONE.to_string(), REASON_NP_NONE.to_string()],
+        }
+    );
+}
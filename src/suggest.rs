@@ -0,0 +1,87 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+/// Number of single character insertions, deletions or substitutions turning `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Returns the word of `vocabulary` that `word` is most likely a misspelling of.
+///
+/// Case and whitespace are ignored and longer words allow more edits, so unrelated short
+/// tags such as `x=` are not matched to whatever happens to be closest.
+pub fn suggest<'a>(word: &str, vocabulary: &[&'a str]) -> Option<&'a str> {
+    if vocabulary.contains(&word) {
+        return None;
+    }
+
+    let normalized = normalize(word);
+    let max_distance = match normalized.chars().count() {
+        0 => return None,
+        1..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    };
+
+    vocabulary
+        .iter()
+        .map(|candidate| {
+            (
+                edit_distance(&normalized, &normalize(candidate)),
+                *candidate,
+            )
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn normalize(word: &str) -> String {
+    word.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// Tests
+#[test]
+fn suggest_edit_distance() {
+    assert_eq!(edit_distance("", ""), 0);
+    assert_eq!(edit_distance("rua", "ruo"), 1);
+    assert_eq!(edit_distance("quarentine", "quarantine"), 1);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("", "afrf"), 4);
+}
+
+#[test]
+fn suggest_closest_word() {
+    let tags = ["v", "p", "pct", "rua", "ruf", "sp", "adkim", "aspf"];
+    let actions = ["none", "quarantine", "reject"];
+
+    assert_eq!(suggest("ruo", &tags), Some("rua"));
+    assert_eq!(suggest("adkm", &tags), Some("adkim"));
+    assert_eq!(suggest("rua", &tags), None);
+    assert_eq!(suggest("xyz", &tags), None);
+    assert_eq!(suggest("quarentine", &actions), Some("quarantine"));
+    assert_eq!(suggest("Rejcet", &actions), Some("reject"));
+    assert_eq!(suggest("block", &actions), None);
+    assert_eq!(suggest("x", &tags), None);
+    assert_eq!(suggest("P", &tags), Some("p"));
+    assert_eq!(suggest("rejct", &actions), Some("reject"));
+    assert_eq!(suggest("DMARC 1", &["DMARC1"]), Some("DMARC1"));
+    assert_eq!(suggest("", &actions), None);
+}
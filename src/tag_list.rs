@@ -0,0 +1,417 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use std::fmt;
+
+const TAG_SEPARATOR: char = ';';
+const VALUE_SEPARATOR: char = '=';
+
+/// Byte offsets into the TXT record a token was read from.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+/// A single `tag=value` pair, see RFC 7489 §6.4 and RFC 6376 §3.2.
+#[derive(Debug, PartialEq)]
+pub struct Tag<'a> {
+    pub name: &'a str,
+    pub value: &'a str,
+    pub name_span: Span,
+    pub value_span: Span,
+}
+
+impl<'a> Tag<'a> {
+    pub fn span(&self) -> Span {
+        Span::new(self.name_span.start, self.value_span.end)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DiagnosticKind {
+    MissingSeparator(String),
+    MissingEquals(String),
+    EmptyValue(String),
+    InvalidTagName(String),
+    VersionNotFirst,
+    VersionMissing,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn new(kind: DiagnosticKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            DiagnosticKind::MissingSeparator(tag) => write!(f, "Missing `;` before `{}`", tag)?,
+            DiagnosticKind::MissingEquals(s) => write!(f, "Missing `=` in `{}`", s)?,
+            DiagnosticKind::EmptyValue(tag) => write!(f, "Empty value for `{}`", tag)?,
+            DiagnosticKind::InvalidTagName(tag) => write!(f, "Invalid tag name `{}`", tag)?,
+            DiagnosticKind::VersionNotFirst => write!(f, "Version tag is not first")?,
+            DiagnosticKind::VersionMissing => write!(f, "Version tag missing")?,
+        }
+
+        write!(f, " at {}", self.span)
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct TagList<'a> {
+    pub tags: Vec<Tag<'a>>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> TagList<'a> {
+    /// Tokenizes `txt` as `tag *WSP "=" *WSP value` entries separated by `*WSP ";" *WSP`.
+    ///
+    /// Parsing never fails, a problem is recorded as a `Diagnostic` and the scanner recovers
+    /// at the next separator so one broken entry does not hide the rest of the record.
+    pub fn parse(txt: &'a str) -> Self {
+        Self::parse_with_base64_tags(txt, &[])
+    }
+
+    /// Like `parse`, but the values of `base64_tags` are never checked for a missing `;`.
+    ///
+    /// Base64 values such as a DKIM `p=` may hold whitespace and end in `=` padding
+    /// (RFC 6376 §3.2), which would otherwise read as the start of another tag.
+    pub fn parse_with_base64_tags(txt: &'a str, base64_tags: &[&str]) -> Self {
+        let mut tag_list = Self::default();
+        let mut start = 0;
+
+        for entry in txt.split(TAG_SEPARATOR) {
+            tag_list.parse_entry(txt, start, start + entry.len(), base64_tags);
+            start += entry.len() + TAG_SEPARATOR.len_utf8();
+        }
+
+        tag_list
+    }
+
+    /// Checks that `version_tag` exists and is the first tag of the record.
+    pub fn check_version_first(&mut self, version_tag: &str) {
+        match self.tags.iter().position(|t| t.name == version_tag) {
+            Some(0) => (),
+            Some(i) => {
+                let span = self.tags[i].span();
+                self.diagnostics
+                    .push(Diagnostic::new(DiagnosticKind::VersionNotFirst, span))
+            }
+            None => self.diagnostics.push(Diagnostic::new(
+                DiagnosticKind::VersionMissing,
+                Span::new(0, 0),
+            )),
+        }
+    }
+
+    /// Whether a missing `;` or `=` blurs where the `name` tag begins or ends, so a receiver
+    /// that does not recover like this parser reads another value for it or none at all.
+    pub fn is_garbled(&self, name: &str) -> bool {
+        self.diagnostics.iter().any(|d| match &d.kind {
+            // Both the recovered tag and the tag whose value it was read from
+            DiagnosticKind::MissingSeparator(_) => self
+                .tags
+                .iter()
+                .position(|t| t.name_span.start == d.span.start)
+                .is_some_and(|i| {
+                    self.tags[i].name == name || (i > 0 && self.tags[i - 1].name == name)
+                }),
+            DiagnosticKind::MissingEquals(entry) => {
+                entry.split(|c: char| !is_tag_name_char(c)).next() == Some(name)
+            }
+            _ => false,
+        })
+    }
+
+    fn parse_entry(&mut self, txt: &'a str, start: usize, end: usize, base64_tags: &[&str]) {
+        let (start, end) = trim_wsp(txt, start, end);
+
+        // Empty entries come from a trailing separator, which the grammar permits
+        if start == end {
+            return;
+        }
+
+        let eq = match txt[start..end].find(VALUE_SEPARATOR) {
+            Some(i) => start + i,
+            None => {
+                self.diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::MissingEquals(txt[start..end].to_string()),
+                    Span::new(start, end),
+                ));
+                return;
+            }
+        };
+
+        let (name_start, name_end) = trim_wsp(txt, start, eq);
+        let (value_start, value_end) = trim_wsp(txt, eq + 1, end);
+
+        // A `tag=value` pair inside the value means the `;` between the two was left out
+        let value_end = match find_missing_separator(txt, value_start, value_end)
+            .filter(|_| !base64_tags.contains(&&txt[name_start..name_end]))
+        {
+            Some(next_start) => {
+                let next_name_end = txt[next_start..end]
+                    .find(|c: char| c == VALUE_SEPARATOR || is_wsp(c))
+                    .map_or(end, |i| next_start + i);
+
+                self.diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::MissingSeparator(txt[next_start..next_name_end].to_string()),
+                    Span::new(next_start, next_name_end),
+                ));
+                self.parse_entry(txt, next_start, end, base64_tags);
+
+                trim_wsp(txt, value_start, next_start).1
+            }
+            None => value_end,
+        };
+
+        let tag = Tag {
+            name: &txt[name_start..name_end],
+            value: &txt[value_start..value_end],
+            name_span: Span::new(name_start, name_end),
+            value_span: Span::new(value_start, value_end),
+        };
+
+        if !is_valid_tag_name(tag.name) {
+            self.diagnostics.push(Diagnostic::new(
+                DiagnosticKind::InvalidTagName(tag.name.to_string()),
+                tag.name_span,
+            ));
+        }
+
+        if tag.value.is_empty() {
+            self.diagnostics.push(Diagnostic::new(
+                DiagnosticKind::EmptyValue(tag.name.to_string()),
+                tag.span(),
+            ));
+        }
+
+        // Recovered entries are parsed first, keep the tags in record order
+        let i = self
+            .tags
+            .iter()
+            .position(|t| t.name_span.start > tag.name_span.start)
+            .unwrap_or(self.tags.len());
+        self.tags.insert(i, tag);
+    }
+}
+
+fn is_wsp(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn trim_wsp(txt: &str, start: usize, end: usize) -> (usize, usize) {
+    let s = &txt[start..end];
+    let leading = s.len() - s.trim_start_matches(is_wsp).len();
+    let trailing = s.len() - s.trim_end_matches(is_wsp).len();
+
+    match leading == s.len() {
+        true => (start, start),
+        false => (start + leading, end - trailing),
+    }
+}
+
+/// tag-name = ALPHA *ALNUMPUNC, ALNUMPUNC = ALPHA / DIGIT / "_"
+fn is_valid_tag_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() => chars.all(is_tag_name_char),
+        _ => false,
+    }
+}
+
+fn is_tag_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Returns the offset of a `tag=` token following whitespace inside a value.
+fn find_missing_separator(txt: &str, start: usize, end: usize) -> Option<usize> {
+    let value = &txt[start..end];
+    let mut offset = 0;
+
+    for word in value.split(is_wsp) {
+        if offset > 0 && !word.is_empty() {
+            if let Some(eq) = word.find(VALUE_SEPARATOR) {
+                if is_valid_tag_name(word[..eq].trim_end_matches(is_wsp)) {
+                    return Some(start + offset);
+                }
+            }
+        }
+        offset += word.len() + 1;
+    }
+
+    None
+}
+
+// Tests
+#[test]
+fn tag_list_parse() {
+    assert_eq!(TagList::parse(""), TagList::default());
+    assert_eq!(TagList::parse(" ; "), TagList::default());
+
+    let tag_list = TagList::parse("v=DMARC1");
+    assert_eq!(
+        tag_list.tags,
+        vec![Tag {
+            name: "v",
+            value: "DMARC1",
+            name_span: Span::new(0, 1),
+            value_span: Span::new(2, 8),
+        }]
+    );
+    assert_eq!(tag_list.diagnostics, vec![]);
+
+    let tag_list = TagList::parse("v = DMARC1 ;\tp=none;");
+    assert_eq!(
+        tag_list.tags,
+        vec![
+            Tag {
+                name: "v",
+                value: "DMARC1",
+                name_span: Span::new(0, 1),
+                value_span: Span::new(4, 10),
+            },
+            Tag {
+                name: "p",
+                value: "none",
+                name_span: Span::new(13, 14),
+                value_span: Span::new(15, 19),
+            }
+        ]
+    );
+    assert_eq!(tag_list.diagnostics, vec![]);
+}
+
+#[test]
+fn tag_list_parse_diagnostics() {
+    let tag_list = TagList::parse("v=DMARC1;p%fsa;");
+    assert_eq!(tag_list.tags.len(), 1);
+    assert_eq!(
+        tag_list.diagnostics,
+        vec![Diagnostic::new(
+            DiagnosticKind::MissingEquals("p%fsa".to_string()),
+            Span::new(9, 14)
+        )]
+    );
+
+    let tag_list = TagList::parse("v=DMARC1 p=none; rua=");
+    assert_eq!(
+        tag_list
+            .tags
+            .iter()
+            .map(|t| (t.name, t.value))
+            .collect::<Vec<_>>(),
+        vec![("v", "DMARC1"), ("p", "none"), ("rua", "")]
+    );
+    assert_eq!(
+        tag_list.diagnostics,
+        vec![
+            Diagnostic::new(
+                DiagnosticKind::MissingSeparator("p".to_string()),
+                Span::new(9, 10)
+            ),
+            Diagnostic::new(
+                DiagnosticKind::EmptyValue("rua".to_string()),
+                Span::new(17, 21)
+            ),
+        ]
+    );
+
+    let tag_list = TagList::parse("1p=none; p-ct=5");
+    assert_eq!(
+        tag_list.diagnostics,
+        vec![
+            Diagnostic::new(
+                DiagnosticKind::InvalidTagName("1p".to_string()),
+                Span::new(0, 2)
+            ),
+            Diagnostic::new(
+                DiagnosticKind::InvalidTagName("p-ct".to_string()),
+                Span::new(9, 13)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn tag_list_parse_base64_tags() {
+    let txt = "v=DKIM1; p=MIGfMA0G CSqGSIb3 AQAB==\tk=rsa";
+
+    let tag_list = TagList::parse_with_base64_tags(txt, &["p"]);
+    assert_eq!(
+        tag_list
+            .tags
+            .iter()
+            .map(|t| (t.name, t.value))
+            .collect::<Vec<_>>(),
+        vec![("v", "DKIM1"), ("p", "MIGfMA0G CSqGSIb3 AQAB==\tk=rsa")]
+    );
+    assert_eq!(tag_list.diagnostics, vec![]);
+
+    assert_eq!(
+        TagList::parse(txt).diagnostics[0].kind,
+        DiagnosticKind::MissingSeparator("AQAB".to_string())
+    );
+}
+
+#[test]
+fn tag_list_is_garbled() {
+    let tag_list = TagList::parse("v=DMARC1; p=reject");
+    assert!(!tag_list.is_garbled("v"));
+    assert!(!tag_list.is_garbled("p"));
+
+    let tag_list = TagList::parse("v=DMARC1 p=reject; rua=mailto:a@example.com");
+    assert!(tag_list.is_garbled("v"));
+    assert!(tag_list.is_garbled("p"));
+    assert!(!tag_list.is_garbled("rua"));
+
+    let tag_list = TagList::parse("v=DMARC1; p reject; sp=none pct=50");
+    assert!(!tag_list.is_garbled("v"));
+    assert!(tag_list.is_garbled("p"));
+    assert!(tag_list.is_garbled("sp"));
+    assert!(tag_list.is_garbled("pct"));
+}
+
+#[test]
+fn tag_list_check_version_first() {
+    let mut tag_list = TagList::parse("v=DMARC1; p=none");
+    tag_list.check_version_first("v");
+    assert_eq!(tag_list.diagnostics, vec![]);
+
+    let mut tag_list = TagList::parse("p=none; v=DMARC1");
+    tag_list.check_version_first("v");
+    assert_eq!(
+        tag_list.diagnostics,
+        vec![Diagnostic::new(
+            DiagnosticKind::VersionNotFirst,
+            Span::new(8, 16)
+        )]
+    );
+
+    let mut tag_list = TagList::parse("p=none");
+    tag_list.check_version_first("v");
+    assert_eq!(
+        tag_list.diagnostics[0].to_string(),
+        "Version tag missing at 0..0"
+    );
+}
@@ -0,0 +1,180 @@
+use crate::parse::join_txt_strings;
+#[cfg(test)]
+use crate::parse::txt_record;
+use crate::tag_list::TagList;
+use crate::uri::{UriFinding, UriList, HTTPS_SCHEME, MAILTO_SCHEME};
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use std::fmt;
+use trust_dns_client::rr::Record;
+
+pub const TLS_RPT_LABEL: &str = "_smtp._tls";
+
+const TLSRPTV1: &str = "TLSRPTv1";
+const V_TAG: &str = "v";
+const RUA_TAG: &str = "rua";
+
+#[derive(Debug, PartialEq)]
+pub enum TlsRptFinding {
+    NoRecord,
+    MultipleRecords(usize),
+    SyntaxError(String),
+    MissingRua,
+    Uri(UriFinding),
+}
+
+impl fmt::Display for TlsRptFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoRecord => write!(f, "No TLS-RPT record: TLS failures go unreported"),
+            Self::MultipleRecords(n) => write!(f, "{} TLS-RPT records: Senders ignore them", n),
+            Self::SyntaxError(s) => write!(f, "{}", s),
+            Self::MissingRua => write!(f, "Missing `{}`", RUA_TAG),
+            Self::Uri(finding) => write!(f, "{}", finding),
+        }
+    }
+}
+
+impl TlsRptFinding {
+    /// Findings that leave senders without anywhere to send reports.
+    pub fn is_invalid(&self) -> bool {
+        match self {
+            Self::NoRecord => false,
+            Self::Uri(finding) => finding.is_invalid(),
+            _ => true,
+        }
+    }
+}
+
+/// The TLS-RPT record of a domain and what is wrong with it.
+#[derive(Debug, Default, PartialEq)]
+pub struct TlsRptCheck {
+    pub rua: UriList,
+    pub findings: Vec<TlsRptFinding>,
+}
+
+impl TlsRptCheck {
+    /// Selects the record from the TXT answers at `_smtp._tls.<domain>`, RFC 8460 §3.
+    pub fn from_answers(r: &[Record]) -> Self {
+        let txts: Vec<String> = r
+            .iter()
+            .filter_map(|record| record.rdata().as_txt())
+            .map(join_txt_strings)
+            .filter(|txt| txt.starts_with(&format!("{}={}", V_TAG, TLSRPTV1)))
+            .collect();
+
+        match txts.len() {
+            0 => Self::new(vec![TlsRptFinding::NoRecord]),
+            1 => Self::from_txt(&txts[0]),
+            n => Self::new(vec![TlsRptFinding::MultipleRecords(n)]),
+        }
+    }
+
+    pub fn from_txt(txt: &str) -> Self {
+        let mut tag_list = TagList::parse(txt);
+        tag_list.check_version_first(V_TAG);
+
+        let mut check = Self::new(
+            tag_list
+                .diagnostics
+                .iter()
+                .map(|d| TlsRptFinding::SyntaxError(d.to_string()))
+                .collect(),
+        );
+
+        let tag = |name: &str| {
+            tag_list
+                .tags
+                .iter()
+                .find(|t| t.name == name)
+                .map(|t| t.value)
+        };
+
+        if let Some(v) = tag(V_TAG).filter(|v| *v != TLSRPTV1) {
+            check.findings.push(TlsRptFinding::SyntaxError(format!(
+                "v={} is not {}",
+                v, TLSRPTV1
+            )));
+        }
+
+        // Reports go to the same mailto and https URIs as DMARC aggregate reports
+        match tag(RUA_TAG) {
+            Some(rua) => {
+                check.rua = UriList::parse(rua, &[MAILTO_SCHEME, HTTPS_SCHEME]);
+                check
+                    .findings
+                    .extend(check.rua.findings.drain(..).map(TlsRptFinding::Uri));
+            }
+            None => check.findings.push(TlsRptFinding::MissingRua),
+        }
+
+        check
+    }
+
+    fn new(findings: Vec<TlsRptFinding>) -> Self {
+        Self {
+            rua: UriList::default(),
+            findings,
+        }
+    }
+}
+
+// Tests
+#[test]
+fn tls_rpt_record() {
+    let check = TlsRptCheck::from_txt(
+        "v=TLSRPTv1; rua=mailto:tlsrpt@example.com,https://reports.example.com/tlsrpt",
+    );
+    assert_eq!(check.findings, vec![]);
+    assert_eq!(
+        check
+            .rua
+            .uris
+            .iter()
+            .map(|u| u.to_string())
+            .collect::<Vec<String>>(),
+        vec![
+            "mailto:tlsrpt@example.com",
+            "https://reports.example.com/tlsrpt"
+        ]
+    );
+
+    let check = TlsRptCheck::from_txt("v=TLSRPTv2; rua=ftp://example.com");
+    assert_eq!(
+        check.findings,
+        vec![
+            TlsRptFinding::SyntaxError("v=TLSRPTv2 is not TLSRPTv1".to_string()),
+            TlsRptFinding::Uri(UriFinding::UnsupportedScheme(
+                "ftp://example.com".to_string()
+            )),
+        ]
+    );
+    assert!(check.findings.iter().all(|f| f.is_invalid()));
+
+    assert_eq!(
+        TlsRptCheck::from_txt("v=TLSRPTv1;").findings,
+        vec![TlsRptFinding::MissingRua]
+    );
+}
+
+#[test]
+fn tls_rpt_record_selection() {
+    let tls_rpt_txt = |txt: &str| txt_record("_smtp._tls.example.com", txt);
+
+    assert_eq!(
+        TlsRptCheck::from_answers(&[tls_rpt_txt("v=spf1 -all")]).findings,
+        vec![TlsRptFinding::NoRecord]
+    );
+    assert_eq!(
+        TlsRptCheck::from_answers(&[
+            tls_rpt_txt("v=TLSRPTv1; rua=mailto:a@example.com"),
+            tls_rpt_txt("v=TLSRPTv1; rua=mailto:b@example.com"),
+        ])
+        .findings,
+        vec![TlsRptFinding::MultipleRecords(2)]
+    );
+    assert_eq!(
+        TlsRptCheck::from_answers(&[tls_rpt_txt("v=TLSRPTv1; rua=mailto:a@example.com")]).findings,
+        vec![]
+    );
+}
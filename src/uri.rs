@@ -0,0 +1,366 @@
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+use std::fmt;
+
+const URI_SEPARATOR: char = ',';
+const SCHEME_SEPARATOR: char = ':';
+const SIZE_SEPARATOR: char = '!';
+
+pub const MAILTO_SCHEME: &str = "mailto";
+pub const HTTPS_SCHEME: &str = "https";
+
+/// Receivers are only required to deliver to the first two URIs, see RFC 7489 §6.2.
+pub const MAX_DMARC_URIS: usize = 2;
+
+#[derive(Debug, PartialEq)]
+pub enum SizeUnit {
+    Kilo,
+    Mega,
+    Giga,
+    Tera,
+}
+
+impl fmt::Display for SizeUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Kilo => write!(f, "k"),
+            Self::Mega => write!(f, "m"),
+            Self::Giga => write!(f, "g"),
+            Self::Tera => write!(f, "t"),
+        }
+    }
+}
+
+/// Maximum report size from a `!` suffix, e.g. `mailto:dmarc@example.com!10m`.
+#[derive(Debug, PartialEq)]
+pub struct SizeLimit {
+    pub size: u64,
+    pub unit: Option<SizeUnit>,
+}
+
+impl fmt::Display for SizeLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.unit {
+            Some(unit) => write!(f, "{}{}", self.size, unit),
+            None => write!(f, "{}", self.size),
+        }
+    }
+}
+
+impl SizeLimit {
+    fn parse(s: &str) -> Option<Self> {
+        let (digits, unit) = match s.char_indices().last() {
+            Some((i, c)) if c.is_ascii_alphabetic() => {
+                let unit = match c.to_ascii_lowercase() {
+                    'k' => SizeUnit::Kilo,
+                    'm' => SizeUnit::Mega,
+                    'g' => SizeUnit::Giga,
+                    't' => SizeUnit::Tera,
+                    _ => return None,
+                };
+                (&s[..i], Some(unit))
+            }
+            _ => (s, None),
+        };
+
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        digits.parse::<u64>().ok().map(|size| Self { size, unit })
+    }
+}
+
+/// A single report destination, see RFC 7489 §6.2.
+#[derive(Debug, PartialEq)]
+pub struct DmarcUri {
+    pub scheme: String,
+    pub address: String,
+    pub host: String,
+    pub size_limit: Option<SizeLimit>,
+}
+
+impl fmt::Display for DmarcUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}{}", self.scheme, SCHEME_SEPARATOR, self.address)?;
+
+        match &self.size_limit {
+            Some(size_limit) => write!(f, "{}{}", SIZE_SEPARATOR, size_limit),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum UriFinding {
+    MissingScheme(String),
+    UnsupportedScheme(String),
+    MalformedMailto(String),
+    MalformedHttps(String),
+    BadSizeLimit(String),
+    TooManyUris { found: usize, max: usize },
+}
+
+impl fmt::Display for UriFinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingScheme(s) => write!(f, "Missing URI scheme `{}`", s),
+            Self::UnsupportedScheme(s) => write!(f, "Unsupported URI scheme `{}`", s),
+            Self::MalformedMailto(s) => write!(f, "Malformed mailto address `{}`", s),
+            Self::MalformedHttps(s) => write!(f, "Malformed https URI `{}`", s),
+            Self::BadSizeLimit(s) => write!(f, "Bad size limit `{}`", s),
+            Self::TooManyUris { found, max } => write!(
+                f,
+                "{} URIs, receivers are only required to use the first {}",
+                found, max
+            ),
+        }
+    }
+}
+
+impl UriFinding {
+    /// Findings for URIs a receiver cannot send reports to.
+    pub fn is_invalid(&self) -> bool {
+        !matches!(self, Self::TooManyUris { .. })
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct UriList {
+    pub uris: Vec<DmarcUri>,
+    pub findings: Vec<UriFinding>,
+}
+
+impl UriList {
+    /// Parses a comma separated list of URIs, keeping only those using one of `schemes`.
+    pub fn parse(val: &str, schemes: &[&str]) -> Self {
+        Self::parse_uris(val, schemes, false)
+    }
+
+    /// Parses a list of DMARC report URIs, which may end in a `!` size limit.
+    pub fn parse_with_size_limits(val: &str, schemes: &[&str]) -> Self {
+        Self::parse_uris(val, schemes, true)
+    }
+
+    fn parse_uris(val: &str, schemes: &[&str], size_limits: bool) -> Self {
+        let mut uri_list = Self::default();
+
+        for uri in val.split(URI_SEPARATOR).map(|u| u.trim()) {
+            if uri.is_empty() {
+                continue;
+            }
+
+            match parse_uri(uri, schemes, size_limits) {
+                Ok(u) => uri_list.uris.push(u),
+                Err(finding) => uri_list.findings.push(finding),
+            }
+        }
+
+        uri_list
+    }
+
+    /// Records a finding when more destinations are listed than receivers must support.
+    pub fn check_max_uris(&mut self, max_uris: usize) {
+        let n = self.uris.len();
+
+        if n > max_uris {
+            self.findings.push(UriFinding::TooManyUris {
+                found: n,
+                max: max_uris,
+            });
+        }
+    }
+
+    pub fn addresses(&self) -> String {
+        self.join(|u| u.address.clone())
+    }
+
+    pub fn hosts(&self) -> String {
+        self.join(|u| u.host.clone())
+    }
+
+    pub fn size_limits(&self) -> String {
+        self.join(|u| {
+            u.size_limit
+                .as_ref()
+                .map_or("-".to_string(), |s| s.to_string())
+        })
+    }
+
+    fn join<F: Fn(&DmarcUri) -> String>(&self, f: F) -> String {
+        self.uris.iter().map(f).collect::<Vec<String>>().join(" ")
+    }
+}
+
+fn parse_uri(uri: &str, schemes: &[&str], size_limits: bool) -> Result<DmarcUri, UriFinding> {
+    let (uri_part, size_limit) = match uri.rfind(SIZE_SEPARATOR).filter(|_| size_limits) {
+        Some(i) => match SizeLimit::parse(&uri[i + 1..]) {
+            Some(size_limit) => (&uri[..i], Some(size_limit)),
+            None => return Err(UriFinding::BadSizeLimit(uri.to_string())),
+        },
+        None => (uri, None),
+    };
+
+    let (scheme, address) = match uri_part.find(SCHEME_SEPARATOR) {
+        Some(i) => (uri_part[..i].to_lowercase(), &uri_part[i + 1..]),
+        None => return Err(UriFinding::MissingScheme(uri.to_string())),
+    };
+
+    if !schemes.contains(&scheme.as_str()) {
+        return Err(UriFinding::UnsupportedScheme(uri.to_string()));
+    }
+
+    let host = match scheme.as_str() {
+        MAILTO_SCHEME => match mailto_host(address) {
+            Some(host) => host,
+            None => return Err(UriFinding::MalformedMailto(uri.to_string())),
+        },
+        HTTPS_SCHEME => match https_host(address) {
+            Some(host) => host,
+            None => return Err(UriFinding::MalformedHttps(uri.to_string())),
+        },
+        _ => return Err(UriFinding::UnsupportedScheme(uri.to_string())),
+    };
+
+    Ok(DmarcUri {
+        scheme,
+        address: address.to_string(),
+        host,
+        size_limit,
+    })
+}
+
+/// Returns the domain of a `local-part@domain` mailto address.
+fn mailto_host(address: &str) -> Option<String> {
+    // Header fields such as `?subject=` are not part of the address
+    let address = address.split('?').next().unwrap_or_default();
+    let mut parts = address.splitn(2, '@');
+
+    match (parts.next(), parts.next()) {
+        (Some(local), Some(domain))
+            if !local.is_empty() && !local.contains(' ') && is_domain_name(domain) =>
+        {
+            Some(domain.trim_end_matches('.').to_lowercase())
+        }
+        _ => None,
+    }
+}
+
+/// Returns the host of a `//host[:port]/path` https address.
+fn https_host(address: &str) -> Option<String> {
+    let authority = address.strip_prefix("//")?.split('/').next()?;
+    let host = authority.rsplit('@').next()?.split(':').next()?;
+
+    match is_domain_name(host) {
+        true => Some(host.trim_end_matches('.').to_lowercase()),
+        false => None,
+    }
+}
+
+pub fn is_domain_name(domain: &str) -> bool {
+    let domain = domain.trim_end_matches('.');
+
+    domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+// Tests
+#[test]
+fn uri_list_parse() {
+    let uri_list = UriList::parse_with_size_limits(
+        "mailto:dmarc@example.com!10m, mailto:reports@Vendor.NET",
+        &[MAILTO_SCHEME],
+    );
+    assert_eq!(
+        uri_list.uris,
+        vec![
+            DmarcUri {
+                scheme: MAILTO_SCHEME.to_string(),
+                address: "dmarc@example.com".to_string(),
+                host: "example.com".to_string(),
+                size_limit: Some(SizeLimit {
+                    size: 10,
+                    unit: Some(SizeUnit::Mega)
+                }),
+            },
+            DmarcUri {
+                scheme: MAILTO_SCHEME.to_string(),
+                address: "reports@Vendor.NET".to_string(),
+                host: "vendor.net".to_string(),
+                size_limit: None,
+            }
+        ]
+    );
+    assert_eq!(uri_list.findings, vec![]);
+    assert_eq!(uri_list.hosts(), "example.com vendor.net");
+    assert_eq!(uri_list.size_limits(), "10m -");
+    assert_eq!(uri_list.uris[0].to_string(), "mailto:dmarc@example.com!10m");
+}
+
+#[test]
+fn uri_list_parse_findings() {
+    let mut uri_list = UriList::parse_with_size_limits(
+        "dmarc@example.com, mailto:dmarc.example.com, https://example.com/dmarc, mailto:a@example.com!10x, mailto:b@example.com, mailto:c@example.com!500",
+        &[MAILTO_SCHEME],
+    );
+    uri_list.check_max_uris(MAX_DMARC_URIS);
+
+    assert_eq!(uri_list.addresses(), "b@example.com c@example.com");
+    assert_eq!(
+        uri_list.findings,
+        vec![
+            UriFinding::MissingScheme("dmarc@example.com".to_string()),
+            UriFinding::MalformedMailto("mailto:dmarc.example.com".to_string()),
+            UriFinding::UnsupportedScheme("https://example.com/dmarc".to_string()),
+            UriFinding::BadSizeLimit("mailto:a@example.com!10x".to_string()),
+        ]
+    );
+
+    uri_list.uris.push(DmarcUri {
+        scheme: MAILTO_SCHEME.to_string(),
+        address: "d@example.com".to_string(),
+        host: "example.com".to_string(),
+        size_limit: None,
+    });
+    uri_list.check_max_uris(MAX_DMARC_URIS);
+    assert_eq!(
+        uri_list.findings.last(),
+        Some(&UriFinding::TooManyUris { found: 3, max: 2 })
+    );
+    assert!(!uri_list.findings.last().unwrap().is_invalid());
+
+    uri_list.check_max_uris(1);
+    assert_eq!(
+        uri_list.findings.last().unwrap().to_string(),
+        "3 URIs, receivers are only required to use the first 1"
+    );
+}
+
+#[test]
+fn uri_list_parse_https() {
+    let uri_list = UriList::parse(
+        "https://user@Reports.example.com:8443/tlsrpt, https:///tlsrpt",
+        &[MAILTO_SCHEME, HTTPS_SCHEME],
+    );
+    assert_eq!(uri_list.hosts(), "reports.example.com");
+    assert_eq!(
+        uri_list.findings,
+        vec![UriFinding::MalformedHttps("https:///tlsrpt".to_string())]
+    );
+
+    // Only DMARC report URIs take a `!` size limit
+    let uri_list = UriList::parse("https://example.com/logo!v2.svg", &[HTTPS_SCHEME]);
+    assert_eq!(uri_list.findings, vec![]);
+    assert_eq!(
+        uri_list.uris[0].to_string(),
+        "https://example.com/logo!v2.svg"
+    );
+}
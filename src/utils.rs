@@ -6,6 +6,22 @@ pub struct Config {
     pub input_domain_file: String,
     pub output_dmarc_file: String,
     pub batch_size: usize,
+    pub public_suffix_list: Option<String>,
+    pub tree_walk: bool,
+    pub dmarcbis: bool,
+    pub parked: bool,
+    pub spf_expansion: bool,
+    pub score_weights: Option<String>,
+    pub check_host: Option<Vec<String>>,
+    pub dkim_discovery: bool,
+    pub dkim_selector_list: Option<String>,
+    pub replace_dkim_selectors: bool,
+    pub dkim_selector_fields: bool,
+    pub mta_sts: bool,
+    pub mta_sts_base_url: Option<String>,
+    pub mta_sts_ca_cert: Option<String>,
+    pub tls_rpt: bool,
+    pub bimi: bool,
 }
 
 impl Config {
@@ -19,7 +35,7 @@ impl Config {
                     .long("input_domain_file")
                     .value_name("DOMAIN_NAME_LIST_CSV")
                     .help("List of domain names to query")
-                    .required(true)
+                    .required_unless("check_host")
                     .takes_value(true),
             )
             .arg(
@@ -28,7 +44,7 @@ impl Config {
                     .long("output_dmarc_file")
                     .value_name("PARSED_DOMAIN_LIST_CSV")
                     .help("output file containing results")
-                    .required(true)
+                    .required_unless("check_host")
                     .takes_value(true),
             )
             .arg(
@@ -40,6 +56,142 @@ impl Config {
                     .required(false)
                     .takes_value(true),
             )
+            .arg(
+                Arg::with_name("public_suffix_list")
+                    .short("s")
+                    .long("public_suffix_list")
+                    .value_name("PUBLIC_SUFFIX_LIST_DAT")
+                    .help("public suffix list to use instead of the bundled copy")
+                    .required(false)
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("tree_walk")
+                    .short("t")
+                    .long("tree_walk")
+                    .help("also discover policies with the DMARCbis DNS tree walk")
+                    .required(false)
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::with_name("dmarcbis")
+                    .short("d")
+                    .long("dmarcbis")
+                    .help("flag tags deprecated by the DMARCbis profile")
+                    .required(false)
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::with_name("parked")
+                    .short("p")
+                    .long("parked")
+                    .help("check the domains send no mail and list the records they are missing")
+                    .required(false)
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::with_name("spf_expansion")
+                    .short("e")
+                    .long("spf_expansion")
+                    .help("follow SPF includes and count the DNS lookups against the RFC 7208 limits")
+                    .required(false)
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::with_name("score_weights")
+                    .short("w")
+                    .long("score_weights")
+                    .value_name("WEIGHTS")
+                    .help("points taken off the score per finding, e.g. bad=5,very_bad=20,invalid=25,no_policy=100")
+                    .required(false)
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("dkim_discovery")
+                    .short("k")
+                    .long("dkim_discovery")
+                    .help("probe each domain for DKIM keys at common selectors")
+                    .required(false)
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::with_name("dkim_selector_list")
+                    .short("l")
+                    .long("dkim_selector_list")
+                    .value_name("SELECTOR_LIST_TXT")
+                    .help("more DKIM selectors to probe, implies --dkim_discovery")
+                    .required(false)
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("replace_dkim_selectors")
+                    .short("x")
+                    .long("replace_dkim_selectors")
+                    .help("probe only the selectors of --dkim_selector_list, not the bundled ones")
+                    .required(false)
+                    .requires("dkim_selector_list")
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::with_name("dkim_selector_fields")
+                    .short("f")
+                    .long("dkim_selector_fields")
+                    .help("read input lines as domain,selector,... and check the DKIM key of each selector")
+                    .required(false)
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::with_name("mta_sts")
+                    .short("m")
+                    .long("mta_sts")
+                    .help("check MTA-STS records and fetch the policies they announce")
+                    .required(false)
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::with_name("mta_sts_base_url")
+                    .short("u")
+                    .long("mta_sts_base_url")
+                    .value_name("URL")
+                    .help("fetch MTA-STS policies from this URL, `{domain}` is replaced by the domain, implies --mta_sts")
+                    .required(false)
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("mta_sts_ca_cert")
+                    .short("a")
+                    .long("mta_sts_ca_cert")
+                    .value_name("CA_CERT_PEM")
+                    .help("extra root certificate to trust when fetching MTA-STS policies")
+                    .required(false)
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("tls_rpt")
+                    .short("r")
+                    .long("tls_rpt")
+                    .help("check the SMTP TLS reporting records of the domains")
+                    .required(false)
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::with_name("bimi")
+                    .short("g")
+                    .long("bimi")
+                    .help("check the BIMI records of the domains and whether their DMARC policy allows a logo")
+                    .required(false)
+                    .takes_value(false),
+            )
+            .arg(
+                Arg::with_name("check_host")
+                    .short("c")
+                    .long("check_host")
+                    .value_names(&["IP", "MAIL_FROM", "HELO"])
+                    .help("evaluate SPF for a sender IP, MAIL FROM and optional HELO, then exit")
+                    .required(false)
+                    .min_values(2)
+                    .max_values(3),
+            )
             .get_matches();
 
         // If batch size exists set it to CLI argument, otherwise set it to default
@@ -64,9 +216,27 @@ impl Config {
         eprintln!("Using batch size of {}", batch_size);
 
         Self {
-            input_domain_file: String::from(args.value_of("input_domain_file").unwrap()),
-            output_dmarc_file: String::from(args.value_of("output_dmarc_file").unwrap()),
+            input_domain_file: String::from(args.value_of("input_domain_file").unwrap_or_default()),
+            output_dmarc_file: String::from(args.value_of("output_dmarc_file").unwrap_or_default()),
             batch_size,
+            public_suffix_list: args.value_of("public_suffix_list").map(String::from),
+            tree_walk: args.is_present("tree_walk"),
+            dmarcbis: args.is_present("dmarcbis"),
+            parked: args.is_present("parked"),
+            spf_expansion: args.is_present("spf_expansion"),
+            score_weights: args.value_of("score_weights").map(String::from),
+            check_host: args
+                .values_of("check_host")
+                .map(|values| values.map(String::from).collect()),
+            dkim_discovery: args.is_present("dkim_discovery"),
+            dkim_selector_list: args.value_of("dkim_selector_list").map(String::from),
+            replace_dkim_selectors: args.is_present("replace_dkim_selectors"),
+            dkim_selector_fields: args.is_present("dkim_selector_fields"),
+            mta_sts: args.is_present("mta_sts"),
+            mta_sts_base_url: args.value_of("mta_sts_base_url").map(String::from),
+            mta_sts_ca_cert: args.value_of("mta_sts_ca_cert").map(String::from),
+            tls_rpt: args.is_present("tls_rpt"),
+            bimi: args.is_present("bimi"),
         }
     }
 }